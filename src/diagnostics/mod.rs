@@ -0,0 +1,560 @@
+//! Rendering of parse and evaluation errors as annotated source snippets
+
+use crate::interpreter::{EvalError, Interpreter, Value};
+use crate::parser::{ParseError, Rule, Span};
+use crate::vm::Vm;
+use pest::error::{ErrorVariant, InputLocation, LineColLocation};
+use std::fmt::Write;
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The program cannot be parsed or executed
+    Error,
+    /// Something suspicious that does not stop execution
+    Warning,
+}
+
+/// Output mode used when rendering diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Plain text without escape sequences
+    Plain,
+    /// Text highlighted with ANSI color codes
+    Colored,
+}
+
+/// A message attached to a region of the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    /// The region being pointed at
+    pub span: Span,
+    /// Text printed next to the underline
+    pub message: String,
+}
+
+/// A user-facing report about a problem in a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// How serious the problem is
+    pub severity: Severity,
+    /// One-line summary shown in the header
+    pub message: String,
+    /// Source regions to underline
+    pub labels: Vec<Label>,
+    /// Additional context shown after the snippet
+    pub notes: Vec<String>,
+    /// Suggestions for fixing the problem
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    /// Creates an error diagnostic with the given summary
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    /// Creates a warning diagnostic with the given summary
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    /// Adds a label underlining `span`
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Adds a note shown after the snippet
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Adds a `help:` suggestion shown after the snippet
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Builds a diagnostic for an evaluation error
+    ///
+    /// `known_names` are the variables in scope when the error happened, or
+    /// the defined functions for an undefined function; they are used to
+    /// suggest a fix for misspelled identifiers.
+    pub fn from_eval_error<'a>(
+        error: &EvalError,
+        known_names: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        match error {
+            EvalError::UndefinedVariable { name, span } => {
+                let diagnostic = Diagnostic::error(format!("undefined variable `{}`", name))
+                    .with_label(*span, "not found in this scope");
+                match suggest_similar(name, known_names) {
+                    Some(candidate) => {
+                        diagnostic.with_help(format!("did you mean `{}`?", candidate))
+                    }
+                    None => diagnostic,
+                }
+            }
+            EvalError::DivisionByZero { op: "**", span } => Diagnostic::error("division by zero")
+                .with_label(*span, "attempt to raise 0 to a negative power")
+                .with_note("a negative power divides 1 by the base, which evaluated to 0"),
+            EvalError::DivisionByZero { op, span } => Diagnostic::error("division by zero")
+                .with_label(*span, "attempt to divide by zero")
+                .with_note(format!("the right-hand side of `{}` evaluated to 0", op)),
+            EvalError::ModuloByZero { span } => Diagnostic::error("modulo by zero")
+                .with_label(*span, "attempt to take the remainder of a division by zero")
+                .with_note("the right-hand side of `%` evaluated to 0"),
+            EvalError::InexactPower { exponent, span } => Diagnostic::error("inexact power")
+                .with_label(
+                    *span,
+                    format!("the exponent {} is not a whole number", exponent),
+                )
+                .with_note("exact arithmetic only raises numbers to whole powers")
+                .with_help(
+                    "use a float operand such as `2.0 ** (1 / 2)` for an approximate result",
+                ),
+            EvalError::InvalidShift { amount, span } => {
+                Diagnostic::error("shift amount out of range")
+                    .with_label(*span, format!("attempt to shift by {}", amount))
+                    .with_note(match amount {
+                        Value::Int(n) if *n >= 0 => "shift amounts must be between 0 and 63",
+                        _ => "shift amounts cannot be negative",
+                    })
+            }
+            EvalError::IndexOutOfBounds { index, len, span } => {
+                Diagnostic::error("index out of range")
+                    .with_label(*span, format!("index {} is out of range", index))
+                    .with_note(match len {
+                        0 => "the length is 0, so there are no elements".to_string(),
+                        len => format!(
+                            "the length is {}, so indexes must be between -{} and {}",
+                            len,
+                            len,
+                            len - 1
+                        ),
+                    })
+            }
+            EvalError::SliceOutOfBounds { bound, len, span } => {
+                Diagnostic::error("slice bound out of range")
+                    .with_label(*span, format!("slice bound {} is out of range", bound))
+                    .with_note(match len {
+                        0 => "the length is 0, so the only slice bound is 0".to_string(),
+                        len => format!(
+                            "the length is {}, so slice bounds must be between -{} and {}",
+                            len, len, len
+                        ),
+                    })
+            }
+            EvalError::MissingKey { key, span } => {
+                Diagnostic::error(format!("missing key `{}`", key))
+                    .with_label(*span, format!("the map has no key `{}`", key))
+                    .with_help("`keys(map)` lists the keys a map has")
+            }
+            EvalError::InvalidSlice { start, end, span } => Diagnostic::error("invalid slice")
+                .with_label(
+                    *span,
+                    format!("slice starts at {} but ends at {}", start, end),
+                )
+                .with_note("the start of a slice cannot come after its end"),
+            EvalError::NegativeExponent { span } => Diagnostic::error("negative exponent")
+                .with_label(*span, "ints cannot be raised to a negative power")
+                .with_help(
+                    "use a float base such as `2.0 ** -1`, or exact division for a rational result",
+                ),
+            EvalError::Overflow { op, span } => Diagnostic::error("integer overflow")
+                .with_label(*span, format!("result of `{}` does not fit in an int", op))
+                .with_note(format!("ints range from {} to {}", i64::MIN, i64::MAX)),
+            EvalError::TypeMismatch {
+                op,
+                left,
+                right,
+                span,
+            } => Diagnostic::error("mismatched types").with_label(
+                *span,
+                format!("cannot apply `{}` to `{}` and `{}`", op, left, right),
+            ),
+            EvalError::TypeError {
+                expected,
+                found,
+                span,
+            } => Diagnostic::error(format!("expected `{}`, found `{}`", expected, found))
+                .with_label(*span, format!("expected `{}`", expected)),
+            EvalError::UndefinedFunction { name, span } => {
+                let diagnostic = Diagnostic::error(format!("undefined function `{}`", name))
+                    .with_label(*span, "called here");
+                match suggest_similar(name, known_names) {
+                    Some(candidate) => {
+                        diagnostic.with_help(format!("did you mean `{}`?", candidate))
+                    }
+                    None => diagnostic
+                        .with_note("functions must be defined with `fn` before they are called"),
+                }
+            }
+            EvalError::ArityMismatch {
+                name,
+                expected,
+                found,
+                span,
+            } => Diagnostic::error(format!(
+                "function `{}` takes {} argument{} but {} {} given",
+                name,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found,
+                if *found == 1 { "was" } else { "were" }
+            ))
+            .with_label(
+                *span,
+                format!(
+                    "expected {} argument{}",
+                    expected,
+                    if *expected == 1 { "" } else { "s" }
+                ),
+            ),
+            EvalError::StackOverflow { depth, span } => {
+                Diagnostic::error("maximum call depth exceeded")
+                    .with_label(
+                        *span,
+                        format!("this call is nested more than {} deep", depth),
+                    )
+                    .with_note("check that every recursive function reaches a base case")
+            }
+            EvalError::HostError {
+                name,
+                message,
+                span,
+            } => Diagnostic::error(format!("call to `{}` failed", name))
+                .with_label(*span, message.clone()),
+            EvalError::RuntimeError(message) => Diagnostic::error(message.clone()),
+        }
+    }
+
+    /// Renders the diagnostic against the source it refers to
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The full text of the program
+    /// * `origin` - Name shown in the location line, usually a file path
+    /// * `mode` - Whether to emit ANSI color codes
+    pub fn render(&self, source: &str, origin: &str, mode: ColorMode) -> String {
+        let paint = |color: &'static str| match mode {
+            ColorMode::Plain => ("", ""),
+            ColorMode::Colored => (color, RESET),
+        };
+        let (accent, accent_end) = match self.severity {
+            Severity::Error => paint(RED),
+            Severity::Warning => paint(YELLOW),
+        };
+        let (blue, blue_end) = paint(BLUE);
+        let (bold, bold_end) = paint(BOLD);
+        let (cyan, cyan_end) = paint(CYAN);
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{accent}{severity}{accent_end}{bold}: {}{bold_end}",
+            self.message
+        );
+
+        //Spans of hand-built trees have no line to show a snippet of
+        let mut labels: Vec<&Label> = self
+            .labels
+            .iter()
+            .filter(|label| label.span.line > 0)
+            .collect();
+        labels.sort_by_key(|label| label.span.start);
+        let gutter = labels
+            .iter()
+            .map(|label| label.span.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let pad = " ".repeat(gutter);
+
+        if let Some(first) = labels.first() {
+            let _ = writeln!(
+                out,
+                "{pad}{blue}-->{blue_end} {origin}:{}:{}",
+                first.span.line, first.span.column
+            );
+            let _ = writeln!(out, "{pad} {blue}|{blue_end}");
+            for label in &labels {
+                let line_text = source.lines().nth(label.span.line - 1).unwrap_or("");
+                let indent = label.span.column.saturating_sub(1);
+                let available = line_text.chars().count().saturating_sub(indent);
+                let width = source
+                    .get(label.span.start..label.span.end)
+                    .map(|text| text.lines().next().unwrap_or("").chars().count())
+                    .unwrap_or(0)
+                    .min(available)
+                    .max(1);
+                let _ = writeln!(
+                    out,
+                    "{blue}{:>gutter$} |{blue_end} {}",
+                    label.span.line, line_text
+                );
+                let _ = write!(
+                    out,
+                    "{pad} {blue}|{blue_end} {}{accent}{}",
+                    " ".repeat(indent),
+                    "^".repeat(width)
+                );
+                if label.message.is_empty() {
+                    let _ = writeln!(out, "{accent_end}");
+                } else {
+                    let _ = writeln!(out, " {}{accent_end}", label.message);
+                }
+            }
+        }
+
+        if !self.notes.is_empty() || !self.help.is_empty() {
+            let _ = writeln!(out, "{pad} {blue}|{blue_end}");
+        }
+        for note in &self.notes {
+            let _ = writeln!(out, "{pad} {blue}={blue_end} {bold}note{bold_end}: {note}");
+        }
+        for help in &self.help {
+            let _ = writeln!(out, "{pad} {blue}={blue_end} {cyan}help{cyan_end}: {help}");
+        }
+
+        out
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        match error {
+            ParseError::PestError(pest_error) => {
+                let (start, end) = match pest_error.location {
+                    InputLocation::Pos(pos) => (pos, pos),
+                    InputLocation::Span(span) => span,
+                };
+                let (line, column) = match pest_error.line_col {
+                    LineColLocation::Pos(pos) => pos,
+                    LineColLocation::Span(start, _) => start,
+                };
+                let span = Span {
+                    start,
+                    end,
+                    line,
+                    column,
+                };
+                let expected = match &pest_error.variant {
+                    ErrorVariant::ParsingError { positives, .. } if !positives.is_empty() => {
+                        let mut names: Vec<String> = Vec::new();
+                        for name in positives.iter().map(describe_rule) {
+                            if !names.contains(&name) {
+                                names.push(name);
+                            }
+                        }
+                        format!("expected {}", join_alternatives(&names))
+                    }
+                    variant => variant.message().into_owned(),
+                };
+                let diagnostic = Diagnostic::error("invalid syntax").with_label(span, expected);
+                match &pest_error.variant {
+                    //The statement before the error is complete but unterminated
+                    ErrorVariant::ParsingError { positives, .. }
+                        if positives.contains(&Rule::semicolon) =>
+                    {
+                        diagnostic.with_help(
+                            "statements other than functions, `if` and loops end with `;`; \
+                             check whether the previous one is missing it",
+                        )
+                    }
+                    _ => diagnostic,
+                }
+            }
+            ParseError::InvalidNumber {
+                literal,
+                reason,
+                span,
+            } => Diagnostic::error(format!("invalid number literal `{}`", literal))
+                .with_label(*span, reason.clone()),
+            ParseError::InvalidEscape {
+                escape,
+                reason,
+                span,
+            } => Diagnostic::error(format!("invalid escape `{}` in string literal", escape))
+                .with_label(*span, reason.clone())
+                .with_help("the supported escapes are \\n, \\t, \\r, \\0, \\\\, \\\", \\{, \\} and \\u{...}"),
+            ParseError::LoopControlOutsideLoop { keyword, span } => {
+                Diagnostic::error(format!("`{}` outside of a loop", keyword))
+                    .with_label(*span, format!("cannot `{}` outside of a loop", keyword))
+                    .with_help("`break` and `continue` may only appear inside a `while` or `for` body")
+            }
+            ParseError::ReturnOutsideFunction { span } => {
+                Diagnostic::error("`return` outside of a function")
+                    .with_label(*span, "cannot `return` outside of a function")
+            }
+            ParseError::NestedFunction { name, span } => {
+                Diagnostic::error(format!("function `{}` is defined inside a block", name))
+                    .with_label(*span, "not allowed here")
+                    .with_help("move the definition to the top level of the program")
+            }
+            ParseError::DuplicateParameter { name, span } => {
+                Diagnostic::error(format!("parameter `{}` is declared more than once", name))
+                    .with_label(*span, "duplicate parameter")
+            }
+            ParseError::ReservedKeyword { keyword, span } => {
+                Diagnostic::error(format!("`{}` is a reserved keyword", keyword))
+                    .with_label(*span, "cannot be used as a name")
+                    .with_help(format!("choose another name, such as `{}_`", keyword))
+            }
+            ParseError::UnexpectedRule(_) | ParseError::UnexpectedEnd { .. } => {
+                Diagnostic::error(error.to_string())
+                    .with_note("the grammar produced a parse tree the parser did not expect")
+            }
+        }
+    }
+}
+
+impl Interpreter {
+    /// Builds a diagnostic for `error`, suggesting names from this interpreter
+    pub fn diagnose(&self, error: &EvalError) -> Diagnostic {
+        match error {
+            EvalError::UndefinedFunction { .. } => Diagnostic::from_eval_error(
+                error,
+                self.functions
+                    .keys()
+                    .map(String::as_str)
+                    .chain(self.native_functions()),
+            ),
+            _ => Diagnostic::from_eval_error(error, self.variables.keys().map(String::as_str)),
+        }
+    }
+}
+
+impl Vm {
+    /// Builds a diagnostic for `error`, suggesting names defined by the last run
+    pub fn diagnose(&self, error: &EvalError) -> Diagnostic {
+        match error {
+            EvalError::UndefinedFunction { .. } => Diagnostic::from_eval_error(
+                error,
+                self.defined_functions()
+                    .map(String::as_str)
+                    .chain(self.native_functions()),
+            ),
+            _ => Diagnostic::from_eval_error(error, self.variables().keys().map(String::as_str)),
+        }
+    }
+}
+
+/// Returns the candidate closest to `name`, if any is close enough to be a typo
+pub(crate) fn suggest_similar<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (strsim::levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Human readable description of a grammar rule, used in "expected ..." messages
+fn describe_rule(rule: &Rule) -> String {
+    match rule {
+        Rule::EOI => "end of input".to_string(),
+        Rule::or_op
+        | Rule::and_op
+        | Rule::eq_op
+        | Rule::cmp_op
+        | Rule::add_op
+        | Rule::mul_op
+        | Rule::pow_op
+        | Rule::bit_or_op
+        | Rule::bit_xor_op
+        | Rule::bit_and_op
+        | Rule::shift_op => "an operator".to_string(),
+        Rule::expression
+        | Rule::conjunction
+        | Rule::bit_or
+        | Rule::bit_xor
+        | Rule::bit_and
+        | Rule::equality
+        | Rule::comparison
+        | Rule::shift
+        | Rule::sum
+        | Rule::term
+        | Rule::unary
+        | Rule::power
+        | Rule::postfix
+        | Rule::index
+        | Rule::slice
+        | Rule::string
+        | Rule::interpolation
+        | Rule::array
+        | Rule::map
+        | Rule::map_entry
+        | Rule::field
+        | Rule::range
+        | Rule::factor
+        | Rule::call
+        | Rule::not_op
+        | Rule::neg_op
+        | Rule::plus_op
+        | Rule::bit_not_op => "an expression".to_string(),
+        Rule::statement
+        | Rule::function_def
+        | Rule::fn_keyword
+        | Rule::return_statement
+        | Rule::return_keyword
+        | Rule::print_statement
+        | Rule::print_keyword
+        | Rule::if_statement
+        | Rule::if_keyword
+        | Rule::while_statement
+        | Rule::while_keyword
+        | Rule::for_statement
+        | Rule::for_keyword
+        | Rule::break_statement
+        | Rule::continue_statement => "a statement".to_string(),
+        Rule::else_keyword => "`else`".to_string(),
+        Rule::in_keyword => "`in`".to_string(),
+        Rule::range_op => "`..`".to_string(),
+        Rule::slice_colon => "`:`".to_string(),
+        Rule::semicolon => "`;`".to_string(),
+        Rule::target_index => "`[`".to_string(),
+        Rule::target_field => "`.`".to_string(),
+        Rule::string_text => "the rest of the string".to_string(),
+        other => format!("{:?}", other).replace('_', " "),
+    }
+}
+
+fn join_alternatives(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [only] => only.clone(),
+        [first, second] => format!("{} or {}", first, second),
+        [init @ .., last] => format!("one of {} or {}", init.join(", "), last),
+    }
+}
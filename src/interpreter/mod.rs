@@ -0,0 +1,730 @@
+//! Tree-walking interpreter for the Tiny Language AST
+
+mod native;
+mod output;
+mod rational;
+mod value;
+
+pub use native::NativeFn;
+pub use output::{BufferSink, OutputSink, StdoutSink};
+pub use rational::Rational;
+pub use value::{BinaryOp, FloatDivisionMode, IntegerDivisionMode, OverflowMode, Value};
+
+pub(crate) use native::Natives;
+pub(crate) use output::format_line;
+pub(crate) use value::{
+    as_float, assign_index, binary_op, bit_not, check_number, index, interpolate, iterate, length,
+    make_map, negate, range, slice, Arithmetic, Iteration,
+};
+
+use crate::parser::{ASTNode, Span, Spanned};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Call depth allowed by default before a call fails with [`EvalError::StackOverflow`]
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// Native stack that must be left when a call starts; below it, the call
+/// runs on a newly allocated segment of [`STACK_SEGMENT_SIZE`] bytes
+const STACK_RED_ZONE: usize = 128 * 1024;
+
+/// Size of the stack segments allocated for deeply nested calls
+const STACK_SEGMENT_SIZE: usize = 1024 * 1024;
+
+/// Interpreter error types
+#[derive(Debug, Error, PartialEq)]
+pub enum EvalError {
+    #[error("Undefined variable '{name}' at {span}")]
+    UndefinedVariable { name: String, span: Span },
+    #[error("Division by zero in '{op}' at {span}")]
+    DivisionByZero { op: &'static str, span: Span },
+    #[error("Modulo by zero at {span}")]
+    ModuloByZero { span: Span },
+    #[error("Negative exponent at {span}")]
+    NegativeExponent { span: Span },
+    #[error("Fractional exponent {exponent} has no exact result at {span}")]
+    InexactPower { exponent: Value, span: Span },
+    #[error("Shift amount {amount} out of range at {span}")]
+    InvalidShift { amount: Value, span: Span },
+    #[error("Index {index} out of range for length {len} at {span}")]
+    IndexOutOfBounds {
+        index: Value,
+        len: usize,
+        span: Span,
+    },
+    #[error("Slice bound {bound} out of range for length {len} at {span}")]
+    SliceOutOfBounds {
+        bound: Value,
+        len: usize,
+        span: Span,
+    },
+    #[error("Key '{key}' not found at {span}")]
+    MissingKey { key: String, span: Span },
+    #[error("Slice starts at {start} but ends at {end} at {span}")]
+    InvalidSlice {
+        start: usize,
+        end: usize,
+        span: Span,
+    },
+    #[error("Integer overflow in '{op}' at {span}")]
+    Overflow { op: &'static str, span: Span },
+    #[error("Cannot apply '{op}' to {left} and {right} at {span}")]
+    TypeMismatch {
+        op: &'static str,
+        left: &'static str,
+        right: &'static str,
+        span: Span,
+    },
+    #[error("Expected {expected}, found {found} at {span}")]
+    TypeError {
+        expected: &'static str,
+        found: &'static str,
+        span: Span,
+    },
+    #[error("Undefined function '{name}' at {span}")]
+    UndefinedFunction { name: String, span: Span },
+    #[error("Function '{name}' takes {expected} argument(s) but {found} were given at {span}")]
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    #[error("Maximum call depth of {depth} exceeded at {span}")]
+    StackOverflow { depth: usize, span: Span },
+    #[error("Error in function '{name}': {message} at {span}")]
+    HostError {
+        name: String,
+        message: String,
+        span: Span,
+    },
+    #[error("Runtime error: {0}")]
+    RuntimeError(String),
+}
+
+/// A user-defined function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// Parameter names in declaration order
+    pub params: Vec<String>,
+    /// Statements run by a call
+    pub body: Vec<Spanned<ASTNode>>,
+    /// Names local to a call, see [`local_names`]
+    locals: Vec<String>,
+}
+
+impl Function {
+    /// Creates a function from its parameters and body
+    pub fn new(params: Vec<String>, body: Vec<Spanned<ASTNode>>) -> Self {
+        let locals = local_names(&params, &body);
+        Self {
+            params,
+            body,
+            locals,
+        }
+    }
+}
+
+/// Names local to a call of a function: its parameters followed by every
+/// variable assigned anywhere in its body or used as the variable of a
+/// `for` loop, in order of first appearance
+///
+/// Any other name used in the body refers to a global variable. Shared by
+/// the interpreter and the bytecode VM so both resolve names the same way.
+pub(crate) fn local_names(params: &[String], body: &[Spanned<ASTNode>]) -> Vec<String> {
+    fn collect(nodes: &[Spanned<ASTNode>], names: &mut Vec<String>) {
+        for node in nodes {
+            match &node.node {
+                ASTNode::Assignment { name, .. } if !names.contains(name) => {
+                    names.push(name.clone());
+                }
+                ASTNode::If {
+                    then_branch,
+                    else_branch,
+                    ..
+                } => {
+                    collect(then_branch, names);
+                    collect(else_branch.as_deref().unwrap_or_default(), names);
+                }
+                ASTNode::While { body, .. } => collect(body, names),
+                ASTNode::For { variable, body, .. } => {
+                    if !names.contains(variable) {
+                        names.push(variable.clone());
+                    }
+                    collect(body, names);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut names = params.to_vec();
+    collect(body, &mut names);
+    names
+}
+
+/// Local state of a function call in progress
+#[derive(Debug)]
+struct Frame {
+    function: Arc<Function>,
+    locals: HashMap<String, Value>,
+}
+
+/// How control leaves a statement
+enum Flow {
+    /// Execution continues with the next statement, carrying the value of
+    /// expression statements
+    Next(Option<Value>),
+    /// A `break` is unwinding to the innermost loop
+    Break,
+    /// A `continue` is unwinding to the innermost loop
+    Continue,
+    /// A `return` is unwinding to the current call
+    Return(Value),
+}
+
+/// Interpreter that executes the AST and maintains variable state
+#[derive(Debug)]
+pub struct Interpreter {
+    /// HashMap storing variable names and their current values
+    pub variables: HashMap<String, Value>,
+    /// HashMap storing function names and their definitions
+    pub functions: HashMap<String, Arc<Function>>,
+    /// Functions implemented by the host application
+    natives: Natives,
+    /// Calls in progress, innermost last
+    frames: Vec<Frame>,
+    /// Number of nested calls allowed
+    max_call_depth: usize,
+    /// Behavior of arithmetic that overflows or divides by zero
+    arithmetic: Arithmetic,
+    /// Destination of `print` statements
+    output: Box<dyn OutputSink>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    /// Creates a new interpreter with empty variable state
+    ///
+    /// The standard library (`abs`, `min`, `max`, `pow`, `isqrt`, `len` and
+    /// `keys`) is registered as native functions.
+    pub fn new() -> Self {
+        Self {
+            natives: Natives::stdlib(),
+            ..Self::without_stdlib()
+        }
+    }
+
+    /// Creates a new interpreter without any native functions
+    ///
+    /// Useful for sandboxed scripts that should only reach the functions the
+    /// host registers explicitly.
+    pub fn without_stdlib() -> Self {
+        Self {
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            natives: Natives::default(),
+            frames: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            arithmetic: Arithmetic::default(),
+            output: Box::new(StdoutSink),
+        }
+    }
+
+    /// Sets how integer arithmetic behaves when a result does not fit in
+    /// an `i64`
+    ///
+    /// Defaults to [`OverflowMode::Checked`], which fails with
+    /// [`EvalError::Overflow`].
+    pub fn set_overflow_mode(&mut self, mode: OverflowMode) {
+        self.arithmetic.overflow = mode;
+    }
+
+    /// Sets what dividing two integers produces
+    ///
+    /// Defaults to [`IntegerDivisionMode::Truncate`]; with
+    /// [`IntegerDivisionMode::Exact`], `7 / 2` evaluates to the rational
+    /// `7/2` and arithmetic on rationals stays exact.
+    pub fn set_integer_division_mode(&mut self, mode: IntegerDivisionMode) {
+        self.arithmetic.integer_division = mode;
+    }
+
+    /// Sets what dividing a float by zero produces
+    ///
+    /// Defaults to [`FloatDivisionMode::Error`], which fails with
+    /// [`EvalError::DivisionByZero`] like integer division does.
+    pub fn set_float_division_mode(&mut self, mode: FloatDivisionMode) {
+        self.arithmetic.float_division = mode;
+    }
+
+    /// Makes a Rust function callable from scripts as `name`
+    ///
+    /// Calls with a number of arguments other than `arity` fail with
+    /// [`EvalError::ArityMismatch`]; an `Err` returned by the function is
+    /// reported as [`EvalError::HostError`]. Registering a name again
+    /// replaces the previous function. Functions defined in a script with
+    /// `fn` take precedence over native functions of the same name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tiny_lang_parser::{parse_program, Interpreter, Value};
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// interpreter.register_fn("double", 1, |args| match &args[0] {
+    ///     Value::Int(n) => Ok(Value::Int(n * 2)),
+    ///     other => Err(format!("cannot double {}", other.type_name())),
+    /// });
+    /// interpreter.eval(&parse_program("x = double(21);").unwrap()).unwrap();
+    /// assert_eq!(interpreter.variables.get("x"), Some(&Value::Int(42)));
+    /// ```
+    pub fn register_fn<F>(&mut self, name: impl Into<String>, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.natives.register(name, arity, function);
+    }
+
+    /// Removes a native function, including standard library functions
+    ///
+    /// Returns `true` if a function with that name was registered.
+    pub fn unregister_fn(&mut self, name: &str) -> bool {
+        self.natives.remove(name)
+    }
+
+    /// Returns the names of the registered native functions
+    pub fn native_functions(&self) -> impl Iterator<Item = &str> {
+        self.natives.names().map(String::as_str)
+    }
+
+    /// Sets how many calls may be nested before a call fails with
+    /// [`EvalError::StackOverflow`]
+    ///
+    /// Defaults to [`DEFAULT_MAX_CALL_DEPTH`]. Nested calls move to stack
+    /// allocated on the heap as the thread's own stack runs out, so the limit
+    /// is bounded by available memory rather than the thread's stack size.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    /// Sends the lines written by `print` statements to `sink`
+    ///
+    /// Defaults to [`StdoutSink`]. Pass a [`BufferSink`] to capture the
+    /// output, or a closure to handle each line as it is printed.
+    pub fn set_output(&mut self, sink: impl OutputSink + 'static) {
+        self.output = Box::new(sink);
+    }
+
+    /// Evaluates a sequence of AST nodes
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - Slice of AST nodes to evaluate
+    ///
+    /// # Errors
+    ///
+    /// Returns `EvalError` if evaluation fails (e.g., undefined variable, division by zero)
+    pub fn eval(&mut self, nodes: &[Spanned<ASTNode>]) -> Result<(), EvalError> {
+        self.frames.clear();
+        self.exec_block(nodes)?;
+        Ok(())
+    }
+
+    /// Evaluates a single statement and returns its value
+    ///
+    /// Useful for interactive sessions that show the result of each
+    /// statement as it is entered. Statements that do not produce a value,
+    /// such as conditionals, loops and function definitions, return `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EvalError` if evaluation fails
+    pub fn eval_statement(&mut self, node: &Spanned<ASTNode>) -> Result<Option<Value>, EvalError> {
+        match self.exec_statement(node)? {
+            Flow::Next(value) => Ok(value),
+            Flow::Break | Flow::Continue | Flow::Return(_) => Ok(None),
+        }
+    }
+
+    /// Executes statements in order until one of them transfers control
+    fn exec_block(&mut self, nodes: &[Spanned<ASTNode>]) -> Result<Flow, EvalError> {
+        for node in nodes {
+            match self.exec_statement(node)? {
+                Flow::Next(_) => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next(None))
+    }
+
+    /// Executes a single statement and reports where control goes next
+    fn exec_statement(&mut self, node: &Spanned<ASTNode>) -> Result<Flow, EvalError> {
+        match &node.node {
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.eval_node(condition)?.as_bool(condition.span)? {
+                    self.exec_block(then_branch)
+                } else if let Some(else_branch) = else_branch {
+                    self.exec_block(else_branch)
+                } else {
+                    Ok(Flow::Next(None))
+                }
+            }
+            ASTNode::While { condition, body } => {
+                while self.eval_node(condition)?.as_bool(condition.span)? {
+                    match self.exec_block(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next(_) | Flow::Continue => {}
+                    }
+                }
+                Ok(Flow::Next(None))
+            }
+            ASTNode::For {
+                variable,
+                iterable,
+                body,
+            } => self.exec_for(variable, iterable, body),
+            ASTNode::FunctionDef { name, params, body } => {
+                let function = Function::new(params.clone(), body.clone());
+                self.functions.insert(name.clone(), Arc::new(function));
+                Ok(Flow::Next(None))
+            }
+            ASTNode::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval_node(value)?,
+                    None => Value::Unit,
+                };
+                Ok(Flow::Return(value))
+            }
+            ASTNode::Print(values) => {
+                let line = self.print_line(values)?;
+                self.output.write_line(&line);
+                Ok(Flow::Next(None))
+            }
+            ASTNode::Break => Ok(Flow::Break),
+            ASTNode::Continue => Ok(Flow::Continue),
+            _ => self.eval_node(node).map(|value| Flow::Next(Some(value))),
+        }
+    }
+
+    /// Evaluates a single AST node and returns its value
+    ///
+    /// Every nested call goes through this function at least once, so arms
+    /// that need many temporaries are handed to helper functions to keep its
+    /// stack frame small.
+    fn eval_node(&mut self, node: &Spanned<ASTNode>) -> Result<Value, EvalError> {
+        match &node.node {
+            ASTNode::Number(n) => Ok(Value::Int(*n)),
+            ASTNode::Float(x) => Ok(Value::Float(*x)),
+            #[cfg(feature = "bigint")]
+            ASTNode::BigNumber(n) => Ok(Value::from(n.clone())),
+            ASTNode::Bool(b) => Ok(Value::Bool(*b)),
+            ASTNode::String(s) => Ok(Value::String(s.clone())),
+            ASTNode::Interpolation(parts) => Ok(interpolate(&self.eval_all(parts)?)),
+            ASTNode::Array(elements) => Ok(Value::Array(self.eval_all(elements)?)),
+            ASTNode::Map(entries) => self.eval_map(entries),
+            ASTNode::Identifier(name) => self.variable(name, node.span).cloned(),
+            ASTNode::Assignment { name, value, .. } => {
+                let val = self.eval_node(value)?;
+                self.scope(name).insert(name.clone(), val.clone());
+                Ok(val)
+            }
+            ASTNode::IndexAssignment { .. } => self.assign_element(node),
+            ASTNode::Call { name, args } => self.call(name, args, node.span),
+            ASTNode::Index { .. } | ASTNode::Slice { .. } => self.eval_index(node),
+            ASTNode::Add(l, r) => self.eval_binary(BinaryOp::Add, l, r, node.span),
+            ASTNode::Sub(l, r) => self.eval_binary(BinaryOp::Sub, l, r, node.span),
+            ASTNode::Mul(l, r) => self.eval_binary(BinaryOp::Mul, l, r, node.span),
+            ASTNode::Div(l, r) => self.eval_binary(BinaryOp::Div, l, r, node.span),
+            ASTNode::Mod(l, r) => self.eval_binary(BinaryOp::Mod, l, r, node.span),
+            ASTNode::Pow(l, r) => self.eval_binary(BinaryOp::Pow, l, r, node.span),
+            ASTNode::BitAnd(l, r) => self.eval_binary(BinaryOp::BitAnd, l, r, node.span),
+            ASTNode::BitOr(l, r) => self.eval_binary(BinaryOp::BitOr, l, r, node.span),
+            ASTNode::BitXor(l, r) => self.eval_binary(BinaryOp::BitXor, l, r, node.span),
+            ASTNode::Shl(l, r) => self.eval_binary(BinaryOp::Shl, l, r, node.span),
+            ASTNode::Shr(l, r) => self.eval_binary(BinaryOp::Shr, l, r, node.span),
+            ASTNode::Eq(l, r) => self.eval_binary(BinaryOp::Eq, l, r, node.span),
+            ASTNode::Ne(l, r) => self.eval_binary(BinaryOp::Ne, l, r, node.span),
+            ASTNode::Lt(l, r) => self.eval_binary(BinaryOp::Lt, l, r, node.span),
+            ASTNode::Le(l, r) => self.eval_binary(BinaryOp::Le, l, r, node.span),
+            ASTNode::Gt(l, r) => self.eval_binary(BinaryOp::Gt, l, r, node.span),
+            ASTNode::Ge(l, r) => self.eval_binary(BinaryOp::Ge, l, r, node.span),
+            ASTNode::And(l, r) => self.eval_logical(false, l, r),
+            ASTNode::Or(l, r) => self.eval_logical(true, l, r),
+            ASTNode::Not(operand)
+            | ASTNode::Neg(operand)
+            | ASTNode::BitNot(operand)
+            | ASTNode::Plus(operand) => self.eval_unary(&node.node, operand, node.span),
+            ASTNode::Range { .. } => unreachable!("ranges only appear in for loops"),
+            ASTNode::If { .. }
+            | ASTNode::While { .. }
+            | ASTNode::For { .. }
+            | ASTNode::FunctionDef { .. }
+            | ASTNode::Return(_)
+            | ASTNode::Print(_)
+            | ASTNode::Break
+            | ASTNode::Continue => {
+                unreachable!("the parser never places statements inside expressions")
+            }
+        }
+    }
+
+    /// Runs a `for` loop, then gives its variable back the value it had
+    /// before the loop, or removes it if it had none
+    fn exec_for(
+        &mut self,
+        variable: &str,
+        iterable: &Spanned<ASTNode>,
+        body: &[Spanned<ASTNode>],
+    ) -> Result<Flow, EvalError> {
+        let iteration = match &iterable.node {
+            ASTNode::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = self.eval_node(start)?;
+                let end = self.eval_node(end)?;
+                range(start, end, *inclusive, iterable.span)?
+            }
+            _ => iterate(self.eval_node(iterable)?, iterable.span)?,
+        };
+
+        let previous = self.scope(variable).get(variable).cloned();
+        let flow = self.run_iterations(variable, iteration, body);
+        match previous {
+            Some(value) => self.scope(variable).insert(variable.to_string(), value),
+            None => self.scope(variable).remove(variable),
+        };
+        flow
+    }
+
+    /// Runs the body of a `for` loop once per value of `iteration`
+    fn run_iterations(
+        &mut self,
+        variable: &str,
+        iteration: Iteration,
+        body: &[Spanned<ASTNode>],
+    ) -> Result<Flow, EvalError> {
+        for value in iteration {
+            self.scope(variable).insert(variable.to_string(), value);
+            match self.exec_block(body)? {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                Flow::Next(_) | Flow::Continue => {}
+            }
+        }
+        Ok(Flow::Next(None))
+    }
+
+    /// Returns the variables `name` refers to: the current call's locals if
+    /// it is local to the running function, the globals otherwise
+    fn scope(&mut self, name: &str) -> &mut HashMap<String, Value> {
+        match self.frames.last_mut() {
+            Some(frame) if frame.function.locals.iter().any(|local| local == name) => {
+                &mut frame.locals
+            }
+            _ => &mut self.variables,
+        }
+    }
+
+    /// Returns the value of the variable `name`, or an error located at `span`
+    /// if it is not defined
+    fn variable(&mut self, name: &str, span: Span) -> Result<&Value, EvalError> {
+        self.scope(name)
+            .get(name)
+            .ok_or_else(|| EvalError::UndefinedVariable {
+                name: name.to_string(),
+                span,
+            })
+    }
+
+    /// Evaluates the arguments, then runs the function `name` in a new frame,
+    /// or hands them to the native function of that name
+    fn call(
+        &mut self,
+        name: &str,
+        args: &[Spanned<ASTNode>],
+        span: Span,
+    ) -> Result<Value, EvalError> {
+        let values = self.eval_all(args)?;
+
+        let Some(function) = self.functions.get(name).cloned() else {
+            return match self.natives.get(name) {
+                Some(native) => native.call(name, &values, self.arithmetic, span),
+                None => Err(EvalError::UndefinedFunction {
+                    name: name.to_string(),
+                    span,
+                }),
+            };
+        };
+        if values.len() != function.params.len() {
+            return Err(EvalError::ArityMismatch {
+                name: name.to_string(),
+                expected: function.params.len(),
+                found: values.len(),
+                span,
+            });
+        }
+        if self.frames.len() >= self.max_call_depth {
+            return Err(EvalError::StackOverflow {
+                depth: self.max_call_depth,
+                span,
+            });
+        }
+
+        let locals = function.params.iter().cloned().zip(values).collect();
+        self.frames.push(Frame {
+            function: function.clone(),
+            locals,
+        });
+        //Deep recursion would overflow the thread's stack long before the
+        //call depth limit, so calls continue on the heap once it runs low
+        let flow = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || {
+            self.exec_block(&function.body)
+        });
+        self.frames.pop();
+
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(Value::Unit),
+        }
+    }
+
+    /// Evaluates the target and the index or slice bounds of an indexing
+    /// expression, then selects the element or slice
+    fn eval_index(&mut self, node: &Spanned<ASTNode>) -> Result<Value, EvalError> {
+        match &node.node {
+            //A variable is indexed where it is stored rather than copied
+            //whole, so reading elements in a loop stays linear
+            ASTNode::Index { target, index: i } => match &target.node {
+                ASTNode::Identifier(name) => {
+                    self.variable(name, target.span)?;
+                    let i = self.eval_node(i)?;
+                    index(self.variable(name, target.span)?, i, node.span)
+                }
+                _ => {
+                    let target = self.eval_node(target)?;
+                    let i = self.eval_node(i)?;
+                    index(&target, i, node.span)
+                }
+            },
+            ASTNode::Slice { target, start, end } => {
+                let target = self.eval_node(target)?;
+                let start = start.as_deref().map(|s| self.eval_node(s)).transpose()?;
+                let end = end.as_deref().map(|e| self.eval_node(e)).transpose()?;
+                slice(target, start, end, node.span)
+            }
+            _ => unreachable!("eval_index is only called for indexing"),
+        }
+    }
+
+    /// Evaluates the indexes and the value of an index assignment, then
+    /// replaces the selected element of the variable's array or map
+    fn assign_element(&mut self, node: &Spanned<ASTNode>) -> Result<Value, EvalError> {
+        let ASTNode::IndexAssignment {
+            name,
+            indexes,
+            value,
+        } = &node.node
+        else {
+            unreachable!("assign_element is only called for index assignments")
+        };
+        let indexes = self.eval_all(indexes)?;
+        let value = self.eval_node(value)?;
+        let Some(target) = self.scope(name).get_mut(name) else {
+            return Err(EvalError::UndefinedVariable {
+                name: name.clone(),
+                span: node.span,
+            });
+        };
+        assign_index(target, indexes, value.clone(), node.span)?;
+        Ok(value)
+    }
+
+    /// Evaluates the key and value of every entry of a map literal in order,
+    /// then builds the map from them
+    fn eval_map(
+        &mut self,
+        entries: &[(Spanned<ASTNode>, Spanned<ASTNode>)],
+    ) -> Result<Value, EvalError> {
+        let mut values = Vec::with_capacity(entries.len() * 2);
+        for (key, value) in entries {
+            values.push(self.eval_node(key)?);
+            values.push(self.eval_node(value)?);
+        }
+        Ok(make_map(values))
+    }
+
+    /// Evaluates the operands of a `print` statement and joins their values
+    /// with spaces
+    fn print_line(&mut self, values: &[Spanned<ASTNode>]) -> Result<String, EvalError> {
+        Ok(format_line(&self.eval_all(values)?))
+    }
+
+    /// Evaluates expressions in order, stopping at the first error
+    fn eval_all(&mut self, nodes: &[Spanned<ASTNode>]) -> Result<Vec<Value>, EvalError> {
+        nodes.iter().map(|node| self.eval_node(node)).collect()
+    }
+
+    /// Evaluates `left`, then `right` only if `left` is not already
+    /// `short_circuit`, the value `||` stops at (`&&` stops at `false`)
+    fn eval_logical(
+        &mut self,
+        short_circuit: bool,
+        left: &Spanned<ASTNode>,
+        right: &Spanned<ASTNode>,
+    ) -> Result<Value, EvalError> {
+        if self.eval_node(left)?.as_bool(left.span)? == short_circuit {
+            return Ok(Value::Bool(short_circuit));
+        }
+        Ok(Value::Bool(self.eval_node(right)?.as_bool(right.span)?))
+    }
+
+    /// Evaluates the operand of the prefix operator `node`, then applies the
+    /// operator to it
+    fn eval_unary(
+        &mut self,
+        node: &ASTNode,
+        operand: &Spanned<ASTNode>,
+        span: Span,
+    ) -> Result<Value, EvalError> {
+        let value = self.eval_node(operand)?;
+        match node {
+            ASTNode::Not(_) => Ok(Value::Bool(!value.as_bool(operand.span)?)),
+            ASTNode::Neg(_) => {
+                check_number(&value, operand.span)?;
+                negate(value, self.arithmetic, span)
+            }
+            ASTNode::BitNot(_) => bit_not(value, operand.span),
+            ASTNode::Plus(_) => {
+                check_number(&value, operand.span)?;
+                Ok(value)
+            }
+            _ => unreachable!("eval_unary is only called for prefix operators"),
+        }
+    }
+
+    /// Evaluates both operands, then applies `op` to them
+    fn eval_binary(
+        &mut self,
+        op: BinaryOp,
+        left: &Spanned<ASTNode>,
+        right: &Spanned<ASTNode>,
+        span: Span,
+    ) -> Result<Value, EvalError> {
+        let left_val = self.eval_node(left)?;
+        let right_val = self.eval_node(right)?;
+        binary_op(op, left_val, right_val, self.arithmetic, span)
+    }
+}
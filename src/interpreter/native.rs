@@ -0,0 +1,247 @@
+//! Functions implemented by the host application and the standard library
+
+use super::{as_float, binary_op, length, negate, Arithmetic, BinaryOp, EvalError, Value};
+use crate::parser::Span;
+#[cfg(feature = "bigint")]
+use num_traits::Signed;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Signature of functions implemented in Rust and callable from scripts
+///
+/// The function receives the evaluated arguments, already checked against
+/// the registered arity. An `Err` message is reported to the script as
+/// [`EvalError::HostError`].
+pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync;
+
+/// Signature of the functions kept in the registry, which also receive the
+/// arithmetic settings of the caller and the span of the call
+type StdFn = dyn Fn(&[Value], Arithmetic, Span) -> Result<Value, String> + Send + Sync;
+
+/// A registered native function
+#[derive(Clone)]
+pub(crate) struct NativeFunction {
+    arity: usize,
+    function: Arc<StdFn>,
+}
+
+impl NativeFunction {
+    /// Checks the argument count, then calls the function
+    ///
+    /// Shared by the interpreter and the bytecode VM so both report the
+    /// same errors. `arithmetic` holds the caller's settings, which the
+    /// standard library follows like the operators do; `span` covers the
+    /// whole call.
+    pub(crate) fn call(
+        &self,
+        name: &str,
+        args: &[Value],
+        arithmetic: Arithmetic,
+        span: Span,
+    ) -> Result<Value, EvalError> {
+        if args.len() != self.arity {
+            return Err(EvalError::ArityMismatch {
+                name: name.to_string(),
+                expected: self.arity,
+                found: args.len(),
+                span,
+            });
+        }
+        (self.function)(args, arithmetic, span).map_err(|message| EvalError::HostError {
+            name: name.to_string(),
+            message,
+            span,
+        })
+    }
+}
+
+/// Native functions available to scripts, keyed by name
+#[derive(Clone, Default)]
+pub(crate) struct Natives {
+    functions: HashMap<String, NativeFunction>,
+}
+
+impl Natives {
+    /// Creates a registry holding the standard library
+    pub(crate) fn stdlib() -> Self {
+        let mut natives = Self::default();
+        //abs and pow handle overflow as the - and ** operators do
+        natives.register_std("abs", 1, |args, arithmetic, span| {
+            match number_arg(args, 0)? {
+                Value::Float(x) => Ok(Value::Float(x.abs())),
+                n if is_negative(n) => negate(n.clone(), arithmetic, span).map_err(|_| overflow()),
+                n => Ok(n.clone()),
+            }
+        });
+        natives.register_std("min", 2, |args, arithmetic, span| {
+            extremum(args, BinaryOp::Lt, arithmetic, span)
+        });
+        natives.register_std("max", 2, |args, arithmetic, span| {
+            extremum(args, BinaryOp::Gt, arithmetic, span)
+        });
+        natives.register_std("pow", 2, |args, arithmetic, span| {
+            let base = integer_arg(args, 0)?.clone();
+            let exponent = u32::try_from(int_arg(args, 1)?)
+                .map_err(|_| "exponent must be between 0 and 4294967295".to_string())?;
+            let exponent = Value::Int(exponent.into());
+            binary_op(BinaryOp::Pow, base, exponent, arithmetic, span).map_err(|_| overflow())
+        });
+        natives.register("len", 1, |args| match length(&args[0]) {
+            Some(len) => Ok(Value::Int(len as i64)),
+            None => Err(format!(
+                "argument 1 must be a string, array or map, found {}",
+                args[0].type_name()
+            )),
+        });
+        natives.register("keys", 1, |args| match &args[0] {
+            Value::Map(entries) => Ok(Value::Array(
+                entries
+                    .keys()
+                    .map(|key| Value::from(key.as_str()))
+                    .collect(),
+            )),
+            other => Err(format!(
+                "argument 1 must be a map, found {}",
+                other.type_name()
+            )),
+        });
+        natives.register("isqrt", 1, |args| match integer_arg(args, 0)? {
+            n if is_negative(n) => {
+                Err("cannot take the square root of a negative number".to_string())
+            }
+            Value::Int(n) => Ok(Value::Int(n.isqrt())),
+            #[cfg(feature = "bigint")]
+            Value::BigInt(n) => Ok(Value::from(n.sqrt())),
+            _ => unreachable!("integer_arg only accepts integers"),
+        });
+        natives
+    }
+
+    /// Adds a function, replacing any function with the same name
+    pub(crate) fn register<F>(&mut self, name: impl Into<String>, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.register_std(name, arity, move |args, _, _| function(args));
+    }
+
+    /// Adds a function that depends on the caller's arithmetic settings
+    fn register_std<F>(&mut self, name: impl Into<String>, arity: usize, function: F)
+    where
+        F: Fn(&[Value], Arithmetic, Span) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.functions.insert(
+            name.into(),
+            NativeFunction {
+                arity,
+                function: Arc::new(function),
+            },
+        );
+    }
+
+    /// Removes a function, returning whether it was registered
+    pub(crate) fn remove(&mut self, name: &str) -> bool {
+        self.functions.remove(name).is_some()
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&NativeFunction> {
+        self.functions.get(name)
+    }
+
+    pub(crate) fn names(&self) -> impl Iterator<Item = &String> {
+        self.functions.keys()
+    }
+}
+
+impl fmt::Debug for Natives {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.functions
+                    .iter()
+                    .map(|(name, function)| (name, function.arity)),
+            )
+            .finish()
+    }
+}
+
+/// Returns the integer argument at `index`, or a message naming its type
+fn int_arg(args: &[Value], index: usize) -> Result<i64, String> {
+    match &args[index] {
+        Value::Int(n) => Ok(*n),
+        #[cfg(feature = "bigint")]
+        Value::BigInt(_) => Err(format!("argument {} does not fit in 64 bits", index + 1)),
+        other => Err(format!(
+            "argument {} must be an int, found {}",
+            index + 1,
+            other.type_name()
+        )),
+    }
+}
+
+/// Returns the integer argument at `index` of any size, or a message naming
+/// its type
+fn integer_arg(args: &[Value], index: usize) -> Result<&Value, String> {
+    match &args[index] {
+        #[cfg(feature = "bigint")]
+        value @ Value::BigInt(_) => Ok(value),
+        value => int_arg(args, index).map(|_| value),
+    }
+}
+
+/// Returns the numeric argument at `index`, or a message naming its type
+fn number_arg(args: &[Value], index: usize) -> Result<&Value, String> {
+    match &args[index] {
+        value @ (Value::Int(_) | Value::Float(_) | Value::Rational(_)) => Ok(value),
+        #[cfg(feature = "bigint")]
+        value @ Value::BigInt(_) => Ok(value),
+        other => Err(format!(
+            "argument {} must be a number, found {}",
+            index + 1,
+            other.type_name()
+        )),
+    }
+}
+
+/// Returns whether a value accepted by [`number_arg`] is below zero
+fn is_negative(value: &Value) -> bool {
+    match value {
+        Value::Int(n) => *n < 0,
+        Value::Float(x) => *x < 0.0,
+        Value::Rational(r) => r.numer() < 0,
+        #[cfg(feature = "bigint")]
+        Value::BigInt(n) => n.is_negative(),
+        _ => unreachable!("number_arg only accepts numbers"),
+    }
+}
+
+/// Returns the smaller (`op` is `<`) or larger (`op` is `>`) of the two
+/// number arguments, preferring the first on a tie
+///
+/// Integers and rationals are compared exactly, whatever their size. Mixing
+/// them with a float promotes the result to a float, as the arithmetic
+/// operators do.
+fn extremum(
+    args: &[Value],
+    op: BinaryOp,
+    arithmetic: Arithmetic,
+    span: Span,
+) -> Result<Value, String> {
+    let (l, r) = (number_arg(args, 0)?, number_arg(args, 1)?);
+    if let (Value::Float(_), _) | (_, Value::Float(_)) = (l, r) {
+        let float = |value| as_float(value).expect("number_arg only accepts numbers");
+        let (l, r) = (float(l), float(r));
+        return Ok(Value::Float(match op {
+            BinaryOp::Lt => l.min(r),
+            _ => l.max(r),
+        }));
+    }
+    let second =
+        binary_op(op, r.clone(), l.clone(), arithmetic, span).map_err(|error| error.to_string())?;
+    Ok(if second == Value::Bool(true) { r } else { l }.clone())
+}
+
+fn overflow() -> String {
+    "result does not fit in an int".to_string()
+}
@@ -0,0 +1,99 @@
+//! Destinations for the output of `print` statements
+
+use super::Value;
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Receives the lines written by `print` statements
+///
+/// Implemented by [`StdoutSink`], [`BufferSink`] and any `FnMut(&str)`
+/// closure, so embedders can route script output wherever they need it.
+pub trait OutputSink: Send {
+    /// Handles one printed line, given without its trailing newline
+    fn write_line(&mut self, line: &str);
+}
+
+impl fmt::Debug for dyn OutputSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OutputSink")
+    }
+}
+
+impl<F: FnMut(&str) + Send> OutputSink for F {
+    fn write_line(&mut self, line: &str) {
+        self(line)
+    }
+}
+
+/// Formats the values of a `print` statement as one line, separated by
+/// spaces
+///
+/// Shared by the interpreter and the bytecode VM so both print the same text.
+pub(crate) fn format_line(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Writes printed lines to the standard output, the default sink
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn write_line(&mut self, line: &str) {
+        //A closed stdout should not abort the script
+        let _ = writeln!(std::io::stdout().lock(), "{}", line);
+    }
+}
+
+/// Collects printed lines in memory
+///
+/// Clones share the same lines, so a clone can be handed to the interpreter
+/// while the original is kept to read the output back.
+///
+/// # Examples
+///
+/// ```
+/// use tiny_lang_parser::{parse_program, BufferSink, Interpreter};
+///
+/// let output = BufferSink::new();
+/// let mut interpreter = Interpreter::new();
+/// interpreter.set_output(output.clone());
+/// interpreter.eval(&parse_program("print 1 + 1, true;").unwrap()).unwrap();
+/// assert_eq!(output.lines(), ["2 true"]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BufferSink {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl BufferSink {
+    /// Creates an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the lines printed so far
+    pub fn lines(&self) -> Vec<String> {
+        self.lock().clone()
+    }
+
+    /// Removes and returns the lines printed so far
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<String>> {
+        //A panic while holding the lock cannot leave the Vec inconsistent
+        self.lines.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl OutputSink for BufferSink {
+    fn write_line(&mut self, line: &str) {
+        self.lock().push(line.to_string());
+    }
+}
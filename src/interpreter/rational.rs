@@ -0,0 +1,165 @@
+//! Exact fractions produced by division in [`IntegerDivisionMode::Exact`]
+//!
+//! [`IntegerDivisionMode::Exact`]: super::IntegerDivisionMode::Exact
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A fraction of two `i64`s in lowest terms
+///
+/// The denominator is always positive and shares no factor with the
+/// numerator, so equal fractions compare equal field by field.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numer: i64,
+    denom: i64,
+}
+
+impl Rational {
+    /// Creates the fraction `numer / denom` in lowest terms
+    ///
+    /// Returns `None` if `denom` is zero or the normalized fraction does not
+    /// fit in `i64`s (only possible with `i64::MIN`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tiny_lang_parser::Rational;
+    ///
+    /// let half = Rational::new(-3, -6).unwrap();
+    /// assert_eq!((half.numer(), half.denom()), (1, 2));
+    /// assert_eq!(half.to_string(), "1/2");
+    /// ```
+    pub fn new(numer: i64, denom: i64) -> Option<Self> {
+        Self::from_i128(numer.into(), denom.into())
+    }
+
+    /// Normalizes a fraction computed with extra headroom
+    pub(crate) fn from_i128(numer: i128, denom: i128) -> Option<Self> {
+        if denom == 0 {
+            return None;
+        }
+        let divisor = gcd(numer.unsigned_abs(), denom.unsigned_abs());
+        let sign = if denom < 0 { -1 } else { 1 };
+        //divisor is at most |denom|, which fits in an i128
+        let divisor = divisor as i128;
+        Some(Self {
+            numer: i64::try_from(sign * (numer / divisor)).ok()?,
+            denom: i64::try_from(sign * (denom / divisor)).ok()?,
+        })
+    }
+
+    /// The numerator, carrying the sign of the fraction
+    pub fn numer(&self) -> i64 {
+        self.numer
+    }
+
+    /// The denominator, always positive
+    pub fn denom(&self) -> i64 {
+        self.denom
+    }
+
+    /// Returns `true` if the denominator is 1
+    pub fn is_integer(&self) -> bool {
+        self.denom == 1
+    }
+
+    /// The nearest float to the fraction
+    pub fn to_f64(&self) -> f64 {
+        self.numer as f64 / self.denom as f64
+    }
+
+    /// Formats the fraction as a decimal with `places` digits after the
+    /// point, rounding halves away from zero
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tiny_lang_parser::Rational;
+    ///
+    /// let third = Rational::new(-2, 3).unwrap();
+    /// assert_eq!(third.to_decimal(2), "-0.67");
+    /// assert_eq!(third.to_decimal(0), "-1");
+    /// ```
+    pub fn to_decimal(&self, places: usize) -> String {
+        let numer = self.numer.unsigned_abs() as u128;
+        let denom = self.denom as u128;
+
+        let mut whole = numer / denom;
+        let mut remainder = numer % denom;
+        let mut digits = Vec::with_capacity(places);
+        for _ in 0..places {
+            remainder *= 10;
+            digits.push((remainder / denom) as u8);
+            remainder %= denom;
+        }
+
+        if remainder * 2 >= denom {
+            //Propagate the carry through trailing nines into the whole part
+            let mut carry = true;
+            for digit in digits.iter_mut().rev() {
+                if *digit == 9 {
+                    *digit = 0;
+                } else {
+                    *digit += 1;
+                    carry = false;
+                    break;
+                }
+            }
+            if carry {
+                whole += 1;
+            }
+        }
+
+        let negative = self.numer < 0 && (whole != 0 || digits.iter().any(|&d| d != 0));
+        let mut text = format!("{}{}", if negative { "-" } else { "" }, whole);
+        if places > 0 {
+            text.push('.');
+            text.extend(digits.iter().map(|&d| char::from(b'0' + d)));
+        }
+        text
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(n: i64) -> Self {
+        Self { numer: n, denom: 1 }
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        //Denominators are positive, so cross-multiplying keeps the order
+        (i128::from(self.numer) * i128::from(other.denom))
+            .cmp(&(i128::from(other.numer) * i128::from(self.denom)))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numer)
+        } else {
+            write!(f, "{}/{}", self.numer, self.denom)
+        }
+    }
+}
+
+impl fmt::Debug for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.numer, self.denom)
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
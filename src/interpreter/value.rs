@@ -0,0 +1,1154 @@
+//! Runtime values and the operators defined on them
+
+use super::{EvalError, Rational};
+use crate::parser::Span;
+#[cfg(feature = "bigint")]
+use num_bigint::{BigInt, Sign};
+#[cfg(feature = "bigint")]
+use num_traits::{ToPrimitive, Zero};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A value produced by evaluating an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A signed 64-bit integer
+    Int(i64),
+    /// An integer outside the range of `i64`
+    ///
+    /// Never holds a number that fits in [`Value::Int`]; use
+    /// [`Value::from`] to build integer values from a `BigInt`.
+    #[cfg(feature = "bigint")]
+    BigInt(BigInt),
+    /// An exact fraction produced by [`IntegerDivisionMode::Exact`]
+    ///
+    /// Never holds a whole number; use [`Value::from`] to build values from
+    /// a `Rational`.
+    Rational(Rational),
+    /// A 64-bit floating-point number
+    Float(f64),
+    /// A boolean
+    Bool(bool),
+    /// Text, indexed and measured in Unicode scalar values
+    String(String),
+    /// An ordered list of values, which may differ in type
+    Array(Vec<Value>),
+    /// Values keyed by string, kept in key order
+    Map(BTreeMap<String, Value>),
+    /// The result of a function that returns no value
+    Unit,
+}
+
+impl Value {
+    /// Name of the value's type as shown in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            #[cfg(feature = "bigint")]
+            Value::BigInt(_) => "int",
+            Value::Rational(_) => "rational",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Unit => "unit",
+        }
+    }
+
+    /// Formats numbers as decimals with `places` digits after the point
+    ///
+    /// Rationals are rounded exactly, halves away from zero, so `7/2` with
+    /// two places is `3.50`. Other values use their usual formatting.
+    pub fn to_decimal_string(&self, places: usize) -> String {
+        match self {
+            Value::Int(n) => Rational::from(*n).to_decimal(places),
+            Value::Rational(r) => r.to_decimal(places),
+            Value::Float(x) => format!("{:.*}", places, x),
+            other => other.to_string(),
+        }
+    }
+
+    /// Returns the boolean inside the value, or a type error located at `span`
+    pub(crate) fn as_bool(&self, span: Span) -> Result<bool, EvalError> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            other => Err(EvalError::TypeError {
+                expected: "bool",
+                found: other.type_name(),
+                span,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            #[cfg(feature = "bigint")]
+            Value::BigInt(n) => write!(f, "{}", n),
+            Value::Rational(r) => write!(f, "{}", r),
+            //Debug keeps the decimal point, so 2.0 is not shown as 2
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_element(f, item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}: ", key)?;
+                    write_element(f, value)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Unit => write!(f, "()"),
+        }
+    }
+}
+
+/// Formats a value inside an array or map
+fn write_element(f: &mut fmt::Formatter<'_>, value: &Value) -> fmt::Result {
+    match value {
+        //Quoted, so ["a, b"] is not mistaken for ["a", "b"]
+        Value::String(s) => write!(f, "{:?}", s),
+        other => write!(f, "{}", other),
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Int(n)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Self {
+        Value::Array(items)
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(entries: BTreeMap<String, Value>) -> Self {
+        Value::Map(entries)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl From<BigInt> for Value {
+    fn from(n: BigInt) -> Self {
+        match i64::try_from(&n) {
+            Ok(n) => Value::Int(n),
+            Err(_) => Value::BigInt(n),
+        }
+    }
+}
+
+impl From<Rational> for Value {
+    fn from(r: Rational) -> Self {
+        if r.is_integer() {
+            Value::Int(r.numer())
+        } else {
+            Value::Rational(r)
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::Float(x)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+/// Binary operators that evaluate both operands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `%`
+    Mod,
+    /// `**`
+    Pow,
+    /// `&`
+    BitAnd,
+    /// `|`
+    BitOr,
+    /// `^`
+    BitXor,
+    /// `<<`
+    Shl,
+    /// `>>`
+    Shr,
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl BinaryOp {
+    /// The operator as written in source code
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Pow => "**",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+        }
+    }
+}
+
+impl BinaryOp {
+    /// Returns `true` for the bitwise and shift operators
+    fn is_bitwise(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::Shl | BinaryOp::Shr
+        )
+    }
+}
+
+/// How integer arithmetic behaves when the result does not fit in an `i64`
+///
+/// Defaults to [`OverflowMode::Checked`], or to `Promote` with the `bigint`
+/// feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowMode {
+    /// Fail with [`EvalError::Overflow`]
+    #[cfg_attr(not(feature = "bigint"), default)]
+    Checked,
+    /// Wrap around at the boundary of the type
+    Wrapping,
+    /// Clamp to `i64::MIN` or `i64::MAX`
+    Saturating,
+    /// Continue with arbitrary precision, giving the exact result
+    #[cfg(feature = "bigint")]
+    #[default]
+    Promote,
+}
+
+impl OverflowMode {
+    /// Applies an integer operation under this mode
+    ///
+    /// `checked`, `wrapping` and `saturating` are the matching `i64`
+    /// methods, e.g. `i64::checked_add`.
+    fn apply(
+        self,
+        op: BinaryOp,
+        (l, r): (i64, i64),
+        checked: fn(i64, i64) -> Option<i64>,
+        wrapping: fn(i64, i64) -> i64,
+        saturating: fn(i64, i64) -> i64,
+        span: Span,
+    ) -> Result<Value, EvalError> {
+        match self {
+            OverflowMode::Checked => checked(l, r).map(Value::Int).ok_or(EvalError::Overflow {
+                op: op.symbol(),
+                span,
+            }),
+            OverflowMode::Wrapping => Ok(Value::Int(wrapping(l, r))),
+            OverflowMode::Saturating => Ok(Value::Int(saturating(l, r))),
+            #[cfg(feature = "bigint")]
+            OverflowMode::Promote => match checked(l, r) {
+                Some(n) => Ok(Value::Int(n)),
+                None => big_binary_op(op, l.into(), r.into(), span),
+            },
+        }
+    }
+}
+
+/// What float division by zero produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatDivisionMode {
+    /// Fail with [`EvalError::DivisionByZero`], like integer division
+    #[default]
+    Error,
+    /// Follow IEEE 754: `1.0 / 0.0` is infinity and `0.0 / 0.0` is NaN
+    Infinity,
+}
+
+/// What dividing two integers produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegerDivisionMode {
+    /// Round toward zero, so `7 / 2` is `3`
+    #[default]
+    Truncate,
+    /// Give the exact [`Value::Rational`], so `7 / 2` is `7/2`
+    ///
+    /// Quotients that are whole numbers stay ints. Rationals are made of two
+    /// `i64`s; results that do not fit fail with [`EvalError::Overflow`]
+    /// whatever the [`OverflowMode`].
+    Exact,
+}
+
+/// Arithmetic settings of an interpreter or VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Arithmetic {
+    pub(crate) overflow: OverflowMode,
+    pub(crate) integer_division: IntegerDivisionMode,
+    pub(crate) float_division: FloatDivisionMode,
+}
+
+/// Applies a binary operator to two values
+///
+/// Shared by the interpreter and the bytecode VM so both produce the same
+/// results and errors. Integer arithmetic follows `arithmetic.overflow`;
+/// `span` covers the whole operation.
+///
+/// If either operand is a float the other is converted and the operation is
+/// done in floating point, so `7 / 2` is `3` but `7 / 2.0` is `3.5`.
+/// Otherwise, if either operand is a rational, the operation is exact.
+///
+/// Integers outside the range of `i64` only come from literals or from
+/// [`OverflowMode::Promote`]; arithmetic involving them is always exact.
+pub(crate) fn binary_op(
+    op: BinaryOp,
+    left: Value,
+    right: Value,
+    arithmetic: Arithmetic,
+    span: Span,
+) -> Result<Value, EvalError> {
+    if op.is_bitwise() {
+        return bitwise_op(op, left, right, arithmetic.overflow, span);
+    }
+
+    if let (Some(l), Some(r)) = (as_float(&left), as_float(&right)) {
+        if matches!(left, Value::Float(_)) || matches!(right, Value::Float(_)) {
+            return float_binary_op(op, l, r, arithmetic.float_division, span);
+        }
+    }
+
+    let exact = arithmetic.integer_division == IntegerDivisionMode::Exact;
+    if let (Some(l), Some(r)) = (as_rational(&left), as_rational(&right)) {
+        let needs_rational = match (op, &left, &right) {
+            (_, Value::Rational(_), _) | (_, _, Value::Rational(_)) => true,
+            //Whole quotients, and the overflowing i64::MIN / -1, stay integral
+            (BinaryOp::Div, Value::Int(l), Value::Int(r)) => {
+                exact && l.checked_rem(*r).is_some_and(|m| m != 0)
+            }
+            (BinaryOp::Pow, Value::Int(_), Value::Int(r)) => exact && *r < 0,
+            _ => false,
+        };
+        if needs_rational {
+            return rational_binary_op(op, l, r, span);
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    {
+        let big = match (&left, &right) {
+            (Value::BigInt(l), Value::BigInt(r)) => Some((l.clone(), r.clone())),
+            (Value::BigInt(l), Value::Int(r)) => Some((l.clone(), (*r).into())),
+            (Value::Int(l), Value::BigInt(r)) => Some(((*l).into(), r.clone())),
+            //Rationals are limited to i64 parts
+            (Value::BigInt(_), Value::Rational(_)) | (Value::Rational(_), Value::BigInt(_)) => {
+                return Err(EvalError::Overflow {
+                    op: op.symbol(),
+                    span,
+                })
+            }
+            _ => None,
+        };
+        if let Some((l, r)) = big {
+            if op == BinaryOp::Div && exact && !r.is_zero() && !(&l % &r).is_zero() {
+                return Err(EvalError::Overflow {
+                    op: op.symbol(),
+                    span,
+                });
+            }
+            return big_binary_op(op, l, r, span);
+        }
+    }
+
+    let overflow = arithmetic.overflow;
+    match (op, &left, &right) {
+        (BinaryOp::Add, Value::Int(l), Value::Int(r)) => overflow.apply(
+            op,
+            (*l, *r),
+            i64::checked_add,
+            i64::wrapping_add,
+            i64::saturating_add,
+            span,
+        ),
+        (BinaryOp::Sub, Value::Int(l), Value::Int(r)) => overflow.apply(
+            op,
+            (*l, *r),
+            i64::checked_sub,
+            i64::wrapping_sub,
+            i64::saturating_sub,
+            span,
+        ),
+        (BinaryOp::Mul, Value::Int(l), Value::Int(r)) => overflow.apply(
+            op,
+            (*l, *r),
+            i64::checked_mul,
+            i64::wrapping_mul,
+            i64::saturating_mul,
+            span,
+        ),
+        (BinaryOp::Div, Value::Int(l), Value::Int(r)) => {
+            if *r == 0 {
+                return Err(EvalError::DivisionByZero {
+                    op: op.symbol(),
+                    span,
+                });
+            }
+            //Only i64::MIN / -1 can overflow
+            overflow.apply(
+                op,
+                (*l, *r),
+                i64::checked_div,
+                i64::wrapping_div,
+                i64::saturating_div,
+                span,
+            )
+        }
+        (BinaryOp::Mod, Value::Int(l), Value::Int(r)) => {
+            if *r == 0 {
+                return Err(EvalError::ModuloByZero { span });
+            }
+            //i64::MIN % -1 is 0, only the intermediate quotient overflows
+            Ok(Value::Int(l.checked_rem(*r).unwrap_or(0)))
+        }
+        (BinaryOp::Pow, Value::Int(l), Value::Int(r)) => {
+            if *r < 0 {
+                return Err(EvalError::NegativeExponent { span });
+            }
+            overflow.apply(
+                op,
+                (*l, *r),
+                checked_pow,
+                wrapping_pow,
+                saturating_pow,
+                span,
+            )
+        }
+        (BinaryOp::Add, Value::String(l), Value::String(r)) => Ok(Value::String(l.clone() + r)),
+        (BinaryOp::Eq, Value::Int(_), Value::Int(_))
+        | (BinaryOp::Eq, Value::Bool(_), Value::Bool(_))
+        | (BinaryOp::Eq, Value::String(_), Value::String(_)) => Ok(Value::Bool(left == right)),
+        (BinaryOp::Ne, Value::Int(_), Value::Int(_))
+        | (BinaryOp::Ne, Value::Bool(_), Value::Bool(_))
+        | (BinaryOp::Ne, Value::String(_), Value::String(_)) => Ok(Value::Bool(left != right)),
+        (BinaryOp::Lt, Value::Int(l), Value::Int(r)) => Ok(Value::Bool(l < r)),
+        (BinaryOp::Le, Value::Int(l), Value::Int(r)) => Ok(Value::Bool(l <= r)),
+        (BinaryOp::Gt, Value::Int(l), Value::Int(r)) => Ok(Value::Bool(l > r)),
+        (BinaryOp::Ge, Value::Int(l), Value::Int(r)) => Ok(Value::Bool(l >= r)),
+        //Strings order by code point, like `str`
+        (BinaryOp::Lt, Value::String(l), Value::String(r)) => Ok(Value::Bool(l < r)),
+        (BinaryOp::Le, Value::String(l), Value::String(r)) => Ok(Value::Bool(l <= r)),
+        (BinaryOp::Gt, Value::String(l), Value::String(r)) => Ok(Value::Bool(l > r)),
+        (BinaryOp::Ge, Value::String(l), Value::String(r)) => Ok(Value::Bool(l >= r)),
+        _ => Err(EvalError::TypeMismatch {
+            op: op.symbol(),
+            left: left.type_name(),
+            right: right.type_name(),
+            span,
+        }),
+    }
+}
+
+/// Negates a number; `-i64::MIN` follows `arithmetic.overflow`
+pub(crate) fn negate(value: Value, arithmetic: Arithmetic, span: Span) -> Result<Value, EvalError> {
+    match value {
+        Value::Float(x) => Ok(Value::Float(-x)),
+        //0 - x reports overflow as `-`, like the operator that caused it
+        Value::Int(_) | Value::Rational(_) => {
+            binary_op(BinaryOp::Sub, Value::Int(0), value, arithmetic, span)
+        }
+        #[cfg(feature = "bigint")]
+        Value::BigInt(n) => Ok(Value::from(-n)),
+        other => Err(EvalError::TypeError {
+            expected: "number",
+            found: other.type_name(),
+            span,
+        }),
+    }
+}
+
+/// Joins the formatted values of the parts of an interpolated string
+pub(crate) fn interpolate(parts: &[Value]) -> Value {
+    Value::String(parts.iter().map(Value::to_string).collect())
+}
+
+/// Builds a map from alternating keys and values, later keys replacing
+/// earlier ones
+pub(crate) fn make_map(entries: Vec<Value>) -> Value {
+    let mut map = BTreeMap::new();
+    let mut entries = entries.into_iter();
+    while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+        map.insert(key.to_string(), value);
+    }
+    Value::Map(map)
+}
+
+/// Returns the element of `target` at `index`
+///
+/// Strings are indexed by Unicode scalar value, giving a one-character
+/// string. Negative indexes count from the end, so `-1` is the last
+/// element. Maps are indexed by string key. `span` covers the whole
+/// indexing expression.
+pub(crate) fn index(target: &Value, index: Value, span: Span) -> Result<Value, EvalError> {
+    if let Value::Map(entries) = target {
+        let key = map_key(&index, span)?;
+        return entries
+            .get(key)
+            .cloned()
+            .ok_or_else(|| EvalError::MissingKey {
+                key: key.to_string(),
+                span,
+            });
+    }
+    let len = length(target).ok_or_else(|| not_indexable(target, "string, array or map", span))?;
+    let element = resolve_index(&index, len, span)?
+        .filter(|&i| i < len)
+        .and_then(|i| match target {
+            Value::String(s) => s.chars().nth(i).map(|c| Value::String(c.to_string())),
+            Value::Array(items) => items.get(i).cloned(),
+            _ => None,
+        });
+    element.ok_or(EvalError::IndexOutOfBounds { index, len, span })
+}
+
+/// Returns the elements of `target` from `start` up to but excluding `end`
+///
+/// Missing bounds default to the start and the end of `target`, and
+/// negative bounds count from the end. Bounds may equal the length, but the
+/// start may not come after the end.
+pub(crate) fn slice(
+    target: Value,
+    start: Option<Value>,
+    end: Option<Value>,
+    span: Span,
+) -> Result<Value, EvalError> {
+    let len = match &target {
+        Value::Map(_) => None,
+        other => length(other),
+    }
+    .ok_or_else(|| not_indexable(&target, "string or array", span))?;
+    let bound = |value: Option<Value>, default: usize| match value {
+        None => Ok(default),
+        Some(bound) => match resolve_index(&bound, len, span)? {
+            Some(i) if i <= len => Ok(i),
+            _ => Err(EvalError::SliceOutOfBounds { bound, len, span }),
+        },
+    };
+    let start = bound(start, 0)?;
+    let end = bound(end, len)?;
+    if start > end {
+        return Err(EvalError::InvalidSlice { start, end, span });
+    }
+    match target {
+        Value::String(s) => Ok(Value::String(
+            s.chars().skip(start).take(end - start).collect(),
+        )),
+        Value::Array(items) => Ok(Value::Array(items[start..end].to_vec())),
+        other => Err(not_indexable(&other, "string or array", span)),
+    }
+}
+
+/// Replaces the element of `target` selected by `indexes`, one index per
+/// level of nesting, with `value`
+///
+/// Indexes follow the rules of [`index`], except that assigning to a
+/// missing key of a map adds it. Strings cannot be changed in place, so
+/// only arrays and maps can be assigned into.
+pub(crate) fn assign_index(
+    target: &mut Value,
+    indexes: Vec<Value>,
+    value: Value,
+    span: Span,
+) -> Result<(), EvalError> {
+    let mut element = target;
+    let mut indexes = indexes.into_iter().peekable();
+    while let Some(index) = indexes.next() {
+        element = match element {
+            Value::Array(items) => {
+                let len = items.len();
+                match resolve_index(&index, len, span)? {
+                    Some(i) if i < len => &mut items[i],
+                    _ => return Err(EvalError::IndexOutOfBounds { index, len, span }),
+                }
+            }
+            Value::Map(entries) => {
+                let key = map_key(&index, span)?;
+                if indexes.peek().is_none() {
+                    entries.insert(key.to_string(), value);
+                    return Ok(());
+                }
+                entries.get_mut(key).ok_or_else(|| EvalError::MissingKey {
+                    key: key.to_string(),
+                    span,
+                })?
+            }
+            other => return Err(not_indexable(other, "array or map", span)),
+        };
+    }
+    *element = value;
+    Ok(())
+}
+
+/// Returns the key inside a map index, or a type error if it is not a
+/// string
+fn map_key(index: &Value, span: Span) -> Result<&str, EvalError> {
+    match index {
+        Value::String(key) => Ok(key),
+        other => Err(EvalError::TypeError {
+            expected: "string",
+            found: other.type_name(),
+            span,
+        }),
+    }
+}
+
+/// Values taken in turn by the variable of a `for` loop
+#[derive(Debug)]
+pub(crate) enum Iteration {
+    /// Ints of a half-open range
+    Range(std::ops::Range<i64>),
+    /// Ints of an inclusive range
+    Inclusive(std::ops::RangeInclusive<i64>),
+    /// Elements of a collection, collected when the loop starts
+    Values(std::vec::IntoIter<Value>),
+}
+
+impl Iterator for Iteration {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self {
+            Iteration::Range(range) => range.next().map(Value::Int),
+            Iteration::Inclusive(range) => range.next().map(Value::Int),
+            Iteration::Values(values) => values.next(),
+        }
+    }
+}
+
+/// Starts iterating over the ints from `start` to `end`, including `end`
+/// if `inclusive` is set; `span` covers the range
+pub(crate) fn range(
+    start: Value,
+    end: Value,
+    inclusive: bool,
+    span: Span,
+) -> Result<Iteration, EvalError> {
+    let bound = |value: Value| match value {
+        Value::Int(n) => Ok(n),
+        #[cfg(feature = "bigint")]
+        Value::BigInt(_) => Err(EvalError::Overflow { op: "..", span }),
+        other => Err(EvalError::TypeError {
+            expected: "int",
+            found: other.type_name(),
+            span,
+        }),
+    };
+    let (start, end) = (bound(start)?, bound(end)?);
+    Ok(if inclusive {
+        Iteration::Inclusive(start..=end)
+    } else {
+        Iteration::Range(start..end)
+    })
+}
+
+/// Starts iterating over the elements of an array, the characters of a
+/// string or the keys of a map, in key order
+///
+/// Later changes to the collection do not affect the iteration.
+pub(crate) fn iterate(collection: Value, span: Span) -> Result<Iteration, EvalError> {
+    let values: Vec<Value> = match collection {
+        Value::Array(items) => items,
+        Value::String(s) => s.chars().map(|c| Value::String(c.to_string())).collect(),
+        Value::Map(entries) => entries.into_keys().map(Value::String).collect(),
+        other => return Err(not_indexable(&other, "string, array or map", span)),
+    };
+    Ok(Iteration::Values(values.into_iter()))
+}
+
+/// Number of elements of a string, array or map, or `None` for other
+/// values
+pub(crate) fn length(value: &Value) -> Option<usize> {
+    match value {
+        Value::String(s) => Some(s.chars().count()),
+        Value::Array(items) => Some(items.len()),
+        Value::Map(entries) => Some(entries.len()),
+        _ => None,
+    }
+}
+
+/// Converts an int index into a position, or `None` if it is negative or
+/// too large to be one
+fn position(index: &Value, span: Span) -> Result<Option<usize>, EvalError> {
+    match index {
+        Value::Int(i) => Ok(usize::try_from(*i).ok()),
+        #[cfg(feature = "bigint")]
+        Value::BigInt(_) => Ok(None),
+        other => Err(EvalError::TypeError {
+            expected: "int",
+            found: other.type_name(),
+            span,
+        }),
+    }
+}
+
+/// Converts an int index into a position among `len` elements, counting
+/// negative indexes from the end, or `None` if it is before the start or too
+/// large to be one
+fn resolve_index(index: &Value, len: usize, span: Span) -> Result<Option<usize>, EvalError> {
+    match index {
+        Value::Int(i) if *i < 0 => Ok(usize::try_from(i.unsigned_abs())
+            .ok()
+            .and_then(|back| len.checked_sub(back))),
+        _ => position(index, span),
+    }
+}
+
+/// Type error for indexing a value that has no elements, where `expected`
+/// names the types that would have worked
+fn not_indexable(target: &Value, expected: &'static str, span: Span) -> EvalError {
+    EvalError::TypeError {
+        expected,
+        found: target.type_name(),
+        span,
+    }
+}
+
+/// Flips every bit of an int
+pub(crate) fn bit_not(value: Value, span: Span) -> Result<Value, EvalError> {
+    match value {
+        Value::Int(n) => Ok(Value::Int(!n)),
+        #[cfg(feature = "bigint")]
+        Value::BigInt(n) => Ok(Value::from(!n)),
+        other => Err(EvalError::TypeError {
+            expected: "int",
+            found: other.type_name(),
+            span,
+        }),
+    }
+}
+
+/// Applies a bitwise or shift operator to two ints, or `&`, `|` and `^`
+/// to two bools
+///
+/// `>>` is arithmetic, copying the sign bit. `<<` multiplies by a power of
+/// two, so results that do not fit in an int follow `overflow` like `*`.
+fn bitwise_op(
+    op: BinaryOp,
+    left: Value,
+    right: Value,
+    overflow: OverflowMode,
+    span: Span,
+) -> Result<Value, EvalError> {
+    #[cfg(feature = "bigint")]
+    {
+        //Promoted ints may be shifted by any amount, like big integers
+        let promoted_shift = matches!(op, BinaryOp::Shl | BinaryOp::Shr)
+            && overflow == OverflowMode::Promote
+            && !matches!(right, Value::Int(0..64));
+        let big =
+            promoted_shift || matches!(left, Value::BigInt(_)) || matches!(right, Value::BigInt(_));
+        if let (true, Some(l), Some(r)) = (big, as_big(&left), as_big(&right)) {
+            return match op {
+                BinaryOp::BitAnd => Ok(Value::from(l & r)),
+                BinaryOp::BitOr => Ok(Value::from(l | r)),
+                BinaryOp::BitXor => Ok(Value::from(l ^ r)),
+                _ => big_shift(op, l, &right, span),
+            };
+        }
+    }
+
+    match (op, &left, &right) {
+        (BinaryOp::Shl, Value::Int(l), Value::Int(_)) => overflow.apply(
+            op,
+            (*l, shift_amount(&right, span)?.into()),
+            checked_shl,
+            wrapping_shl,
+            saturating_shl,
+            span,
+        ),
+        (_, Value::Int(l), Value::Int(r)) => Ok(Value::Int(match op {
+            BinaryOp::BitAnd => l & r,
+            BinaryOp::BitOr => l | r,
+            BinaryOp::BitXor => l ^ r,
+            _ => l >> shift_amount(&right, span)?,
+        })),
+        (BinaryOp::BitAnd, Value::Bool(l), Value::Bool(r)) => Ok(Value::Bool(l & r)),
+        (BinaryOp::BitOr, Value::Bool(l), Value::Bool(r)) => Ok(Value::Bool(l | r)),
+        (BinaryOp::BitXor, Value::Bool(l), Value::Bool(r)) => Ok(Value::Bool(l ^ r)),
+        _ => Err(EvalError::TypeMismatch {
+            op: op.symbol(),
+            left: left.type_name(),
+            right: right.type_name(),
+            span,
+        }),
+    }
+}
+
+/// Returns the int right operand of a shift of an `i64`, which must be in
+/// `0..64`
+fn shift_amount(amount: &Value, span: Span) -> Result<u32, EvalError> {
+    match amount {
+        Value::Int(n @ 0..64) => Ok(*n as u32),
+        _ => Err(EvalError::InvalidShift {
+            amount: amount.clone(),
+            span,
+        }),
+    }
+}
+
+/// `l` shifted left by an `amount` in `0..64`, or `None` if the result does
+/// not fit in an `i64`
+fn checked_shl(l: i64, amount: i64) -> Option<i64> {
+    //The bits shifted out and the new sign bit must all be copies of the
+    //old sign bit
+    let sign_bits = if l < 0 {
+        l.leading_ones()
+    } else {
+        l.leading_zeros()
+    };
+    (i64::from(sign_bits) > amount).then(|| l << amount)
+}
+
+/// `l` shifted left by an `amount` in `0..64`, discarding the bits shifted out
+fn wrapping_shl(l: i64, amount: i64) -> i64 {
+    l << amount
+}
+
+/// `l` shifted left by an `amount` in `0..64`, clamped on overflow
+fn saturating_shl(l: i64, amount: i64) -> i64 {
+    checked_shl(l, amount).unwrap_or(if l < 0 { i64::MIN } else { i64::MAX })
+}
+
+/// Returns a number unchanged, or a type error located at `span`
+pub(crate) fn check_number(value: &Value, span: Span) -> Result<(), EvalError> {
+    match as_float(value) {
+        Some(_) => Ok(()),
+        None => Err(EvalError::TypeError {
+            expected: "number",
+            found: value.type_name(),
+            span,
+        }),
+    }
+}
+
+/// `base` raised to a non-negative `exponent`, or `None` on overflow
+fn checked_pow(mut base: i64, mut exponent: i64) -> Option<i64> {
+    //Exponentiation by squaring handles exponents beyond u32 for 0, 1 and -1
+    let mut result: i64 = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.checked_mul(base)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = base.checked_mul(base)?;
+        }
+    }
+    Some(result)
+}
+
+/// `base` raised to a non-negative `exponent`, wrapping around on overflow
+fn wrapping_pow(mut base: i64, mut exponent: i64) -> i64 {
+    let mut result: i64 = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        exponent >>= 1;
+        base = base.wrapping_mul(base);
+    }
+    result
+}
+
+/// `base` raised to a non-negative `exponent`, clamped on overflow
+fn saturating_pow(base: i64, exponent: i64) -> i64 {
+    checked_pow(base, exponent).unwrap_or(if base < 0 && exponent % 2 == 1 {
+        i64::MIN
+    } else {
+        i64::MAX
+    })
+}
+
+/// Returns a numeric value as a float, or `None` for other types
+pub(crate) fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(n) => Some(*n as f64),
+        Value::Rational(r) => Some(r.to_f64()),
+        #[cfg(feature = "bigint")]
+        Value::BigInt(n) => n.to_f64(),
+        Value::Float(x) => Some(*x),
+        _ => None,
+    }
+}
+
+/// Returns an int or rational as a rational, or `None` for other types
+fn as_rational(value: &Value) -> Option<Rational> {
+    match value {
+        Value::Int(n) => Some(Rational::from(*n)),
+        Value::Rational(r) => Some(*r),
+        _ => None,
+    }
+}
+
+/// Applies a binary operator to two rationals exactly
+fn rational_binary_op(
+    op: BinaryOp,
+    l: Rational,
+    r: Rational,
+    span: Span,
+) -> Result<Value, EvalError> {
+    let (a, b) = (i128::from(l.numer()), i128::from(l.denom()));
+    let (c, d) = (i128::from(r.numer()), i128::from(r.denom()));
+    //Products of two i64s cannot overflow an i128, nor can their sum
+    let result = match op {
+        BinaryOp::Add => Rational::from_i128(a * d + c * b, b * d),
+        BinaryOp::Sub => Rational::from_i128(a * d - c * b, b * d),
+        BinaryOp::Mul => Rational::from_i128(a * c, b * d),
+        BinaryOp::Div => {
+            if c == 0 {
+                return Err(EvalError::DivisionByZero {
+                    op: op.symbol(),
+                    span,
+                });
+            }
+            Rational::from_i128(a * d, b * c)
+        }
+        BinaryOp::Mod => {
+            if c == 0 {
+                return Err(EvalError::ModuloByZero { span });
+            }
+            //Both sides over the common denominator b * d
+            Rational::from_i128((a * d) % (c * b), b * d)
+        }
+        BinaryOp::Pow => {
+            //Roots are generally irrational, and exact arithmetic never
+            //rounds to a float behind the script's back
+            if !r.is_integer() {
+                return Err(EvalError::InexactPower {
+                    exponent: Value::Rational(r),
+                    span,
+                });
+            }
+            if c < 0 && a == 0 {
+                return Err(EvalError::DivisionByZero {
+                    op: op.symbol(),
+                    span,
+                });
+            }
+            //Only |i64::MIN| does not fit, and an even exponent keeps its parity
+            let exponent = r.numer().unsigned_abs().try_into().unwrap_or(i64::MAX - 1);
+            let numer = checked_pow(l.numer(), exponent);
+            let denom = checked_pow(l.denom(), exponent);
+            match (numer, denom) {
+                (Some(numer), Some(denom)) if c < 0 => Rational::new(denom, numer),
+                (Some(numer), Some(denom)) => Rational::new(numer, denom),
+                _ => None,
+            }
+        }
+        BinaryOp::Eq => return Ok(Value::Bool(l == r)),
+        BinaryOp::Ne => return Ok(Value::Bool(l != r)),
+        BinaryOp::Lt => return Ok(Value::Bool(l < r)),
+        BinaryOp::Le => return Ok(Value::Bool(l <= r)),
+        BinaryOp::Gt => return Ok(Value::Bool(l > r)),
+        BinaryOp::Ge => return Ok(Value::Bool(l >= r)),
+        BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::Shl | BinaryOp::Shr => {
+            unreachable!("bitwise operators are applied by bitwise_op")
+        }
+    };
+    result.map(Value::from).ok_or(EvalError::Overflow {
+        op: op.symbol(),
+        span,
+    })
+}
+
+/// Returns an integer as a `BigInt`, or `None` for other types
+#[cfg(feature = "bigint")]
+fn as_big(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::Int(n) => Some((*n).into()),
+        Value::BigInt(n) => Some(n.clone()),
+        _ => None,
+    }
+}
+
+/// Applies a binary operator to two floats
+fn float_binary_op(
+    op: BinaryOp,
+    l: f64,
+    r: f64,
+    division: FloatDivisionMode,
+    span: Span,
+) -> Result<Value, EvalError> {
+    Ok(match op {
+        BinaryOp::Add => Value::Float(l + r),
+        BinaryOp::Sub => Value::Float(l - r),
+        BinaryOp::Mul => Value::Float(l * r),
+        BinaryOp::Div => {
+            if r == 0.0 && division == FloatDivisionMode::Error {
+                return Err(EvalError::DivisionByZero {
+                    op: op.symbol(),
+                    span,
+                });
+            }
+            Value::Float(l / r)
+        }
+        BinaryOp::Mod => {
+            if r == 0.0 && division == FloatDivisionMode::Error {
+                return Err(EvalError::ModuloByZero { span });
+            }
+            Value::Float(l % r)
+        }
+        BinaryOp::Pow => Value::Float(l.powf(r)),
+        BinaryOp::Eq => Value::Bool(l == r),
+        BinaryOp::Ne => Value::Bool(l != r),
+        BinaryOp::Lt => Value::Bool(l < r),
+        BinaryOp::Le => Value::Bool(l <= r),
+        BinaryOp::Gt => Value::Bool(l > r),
+        BinaryOp::Ge => Value::Bool(l >= r),
+        BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::Shl | BinaryOp::Shr => {
+            unreachable!("bitwise operators are applied by bitwise_op")
+        }
+    })
+}
+
+/// Largest result of `**` and `<<` on big integers, in bits
+#[cfg(feature = "bigint")]
+const MAX_BIG_BITS: u64 = 1 << 24;
+
+/// Shifts a big integer by any non-negative `amount`
+#[cfg(feature = "bigint")]
+fn big_shift(op: BinaryOp, l: BigInt, amount: &Value, span: Span) -> Result<Value, EvalError> {
+    //Amounts beyond u64 are clamped, which still shifts every bit out
+    let bits = match amount {
+        &Value::Int(n) if n >= 0 => n.unsigned_abs(),
+        Value::BigInt(n) if n.sign() != Sign::Minus => n.to_u64().unwrap_or(u64::MAX),
+        _ => {
+            return Err(EvalError::InvalidShift {
+                amount: amount.clone(),
+                span,
+            })
+        }
+    };
+    if op == BinaryOp::Shr {
+        return Ok(Value::from(l >> bits));
+    }
+    //Results of more than MAX_BIG_BITS bits are reported as overflow
+    //rather than exhausting memory
+    if !l.is_zero() && l.bits().saturating_add(bits) > MAX_BIG_BITS {
+        return Err(EvalError::Overflow {
+            op: op.symbol(),
+            span,
+        });
+    }
+    Ok(Value::from(l << bits))
+}
+
+/// Applies a binary operator to two integers with arbitrary precision
+#[cfg(feature = "bigint")]
+fn big_binary_op(op: BinaryOp, l: BigInt, r: BigInt, span: Span) -> Result<Value, EvalError> {
+    Ok(match op {
+        BinaryOp::Add => Value::from(l + r),
+        BinaryOp::Sub => Value::from(l - r),
+        BinaryOp::Mul => Value::from(l * r),
+        BinaryOp::Div => {
+            if r.is_zero() {
+                return Err(EvalError::DivisionByZero {
+                    op: op.symbol(),
+                    span,
+                });
+            }
+            Value::from(l / r)
+        }
+        BinaryOp::Mod => {
+            if r.is_zero() {
+                return Err(EvalError::ModuloByZero { span });
+            }
+            Value::from(l % r)
+        }
+        BinaryOp::Pow => {
+            if r.sign() == Sign::Minus {
+                return Err(EvalError::NegativeExponent { span });
+            }
+            //Results of more than MAX_BIG_BITS bits are reported as overflow
+            //rather than exhausting memory
+            let exponent = u32::try_from(&r)
+                .ok()
+                .filter(|&e| l.bits().saturating_mul(u64::from(e)) <= MAX_BIG_BITS);
+            match exponent {
+                Some(exponent) => Value::from(l.pow(exponent)),
+                None => {
+                    return Err(EvalError::Overflow {
+                        op: op.symbol(),
+                        span,
+                    })
+                }
+            }
+        }
+        //Reached when an int shifted left by less than 64 bits is promoted
+        BinaryOp::Shl => return big_shift(op, l, &Value::from(r), span),
+        BinaryOp::Eq => Value::Bool(l == r),
+        BinaryOp::Ne => Value::Bool(l != r),
+        BinaryOp::Lt => Value::Bool(l < r),
+        BinaryOp::Le => Value::Bool(l <= r),
+        BinaryOp::Gt => Value::Bool(l > r),
+        BinaryOp::Ge => Value::Bool(l >= r),
+        BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::Shr => {
+            unreachable!("bitwise operators are applied by bitwise_op")
+        }
+    })
+}
@@ -14,9 +14,25 @@
 //! interpreter.eval(&ast).unwrap();
 //! ```
 
+mod diagnostics;
+mod interpreter;
 mod parser;
+mod repl;
+mod vm;
 
-pub use parser::{parse_program, ASTNode, EvalError, Interpreter, ParseError};
+#[cfg(feature = "bigint")]
+pub use num_bigint::BigInt;
+
+pub use diagnostics::{ColorMode, Diagnostic, Label, Severity};
+pub use interpreter::{
+    BinaryOp, BufferSink, EvalError, FloatDivisionMode, Function, IntegerDivisionMode, Interpreter,
+    NativeFn, OutputSink, OverflowMode, Rational, StdoutSink, Value, DEFAULT_MAX_CALL_DEPTH,
+};
+pub use parser::{
+    parse_program, parse_program_recovering, ASTNode, Child, ParseError, Span, Spanned, KEYWORDS,
+};
+pub use repl::{Repl, Reply};
+pub use vm::{compile, Chunk, CompiledFunction, Instruction, Slot, Vm};
 
 /// Main parsing function that takes source code and returns AST
 ///
@@ -30,6 +46,6 @@ pub use parser::{parse_program, ASTNode, EvalError, Interpreter, ParseError};
 /// let ast = parse_program("x = 5 + 3;").unwrap();
 /// assert!(!ast.is_empty());
 /// ```
-pub fn parse(input: &str) -> Result<Vec<ASTNode>, ParseError> {
+pub fn parse(input: &str) -> Result<Vec<Spanned<ASTNode>>, ParseError> {
     parse_program(input)
 }
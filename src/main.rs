@@ -1,24 +1,121 @@
 //!Tiny Language Parser CLI
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
 use std::fs;
-use tiny_lang_parser::{parse_program, Interpreter};
+use std::io::IsTerminal;
+use std::process;
+use tiny_lang_parser::{
+    compile, parse_program_recovering, ColorMode, Diagnostic, FloatDivisionMode,
+    IntegerDivisionMode, Interpreter, OverflowMode, Repl, Reply, Vm,
+};
 
 #[derive(Parser)]
 #[command(name = "tiny-lang-parser")]
 #[command(about = "A parser and interpreter for Tiny Language", long_about = None)]
 struct Cli {
+    ///When to color error output
+    #[arg(long, value_enum, global = true, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    ///Color output when stderr is a terminal
+    Auto,
+    ///Always color output
+    Always,
+    ///Never color output
+    Never,
+}
+
+impl ColorChoice {
+    fn mode(self) -> ColorMode {
+        let colored = match self {
+            ColorChoice::Auto => {
+                std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        if colored {
+            ColorMode::Colored
+        } else {
+            ColorMode::Plain
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OverflowChoice {
+    ///Report an error
+    Checked,
+    ///Wrap around
+    Wrapping,
+    ///Clamp to the smallest or largest int
+    Saturating,
+    ///Continue with arbitrary precision
+    #[cfg(feature = "bigint")]
+    Promote,
+}
+
+impl From<OverflowChoice> for OverflowMode {
+    fn from(choice: OverflowChoice) -> Self {
+        match choice {
+            OverflowChoice::Checked => OverflowMode::Checked,
+            OverflowChoice::Wrapping => OverflowMode::Wrapping,
+            OverflowChoice::Saturating => OverflowMode::Saturating,
+            #[cfg(feature = "bigint")]
+            OverflowChoice::Promote => OverflowMode::Promote,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FloatDivisionChoice {
+    ///Report an error
+    Error,
+    ///Produce infinity or NaN
+    Infinity,
+}
+
+impl From<FloatDivisionChoice> for FloatDivisionMode {
+    fn from(choice: FloatDivisionChoice) -> Self {
+        match choice {
+            FloatDivisionChoice::Error => FloatDivisionMode::Error,
+            FloatDivisionChoice::Infinity => FloatDivisionMode::Infinity,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     ///Parse and execute a Tiny Language file
     Parse {
         ///Path to the file to parse
         file: String,
+        ///Execute with the bytecode VM instead of the tree-walking interpreter
+        #[arg(long)]
+        vm: bool,
+        ///How integer arithmetic behaves when a result does not fit in an int
+        ///[default: checked, or promote when built with the bigint feature]
+        #[arg(long, value_enum)]
+        overflow: Option<OverflowChoice>,
+        ///What dividing a float by zero produces
+        #[arg(long, value_enum, default_value = "error")]
+        float_division: FloatDivisionChoice,
+        ///Divide integers exactly, producing rationals such as 7/2
+        #[arg(long)]
+        exact: bool,
+        ///Print numeric variables as decimals with this many digits after the point
+        #[arg(long, value_name = "PLACES")]
+        decimals: Option<usize>,
     },
+    ///Start an interactive session
+    Repl,
     ///Display help information
     ParserHelp,
     ///Display credits and authorship information  
@@ -27,26 +124,87 @@ enum Commands {
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    let color = cli.color.mode();
 
     match cli.command {
-        Commands::Parse { file } => {
+        Commands::Parse {
+            file,
+            vm,
+            overflow,
+            float_division,
+            exact,
+            decimals,
+        } => {
+            let overflow = overflow.map_or_else(OverflowMode::default, OverflowMode::from);
+            let float_division = FloatDivisionMode::from(float_division);
+            let integer_division = if exact {
+                IntegerDivisionMode::Exact
+            } else {
+                IntegerDivisionMode::Truncate
+            };
             let content = fs::read_to_string(&file)
                 .map_err(|e| format!("Failed to read file {}: {}", file, e))?;
 
             println!("Parsing file: {}", file);
             println!("Source code:\n{}", content);
 
-            let ast = parse_program(&content).map_err(|e| format!("Parse error: {}", e))?;
+            let (ast, errors) = parse_program_recovering(&content);
+            if !errors.is_empty() {
+                for e in &errors {
+                    let diagnostic = Diagnostic::from(e);
+                    eprintln!("{}", diagnostic.render(&content, &file, color));
+                }
+                eprintln!(
+                    "error: could not parse {} due to {} previous error{}",
+                    file,
+                    errors.len(),
+                    if errors.len() == 1 { "" } else { "s" }
+                );
+                process::exit(1);
+            }
 
             println!("\nAST: {:#?}", ast);
 
-            let mut interpreter = Interpreter::new();
-            interpreter
-                .eval(&ast)
-                .map_err(|e| format!("Evaluation error: {}", e))?;
+            let variables = if vm {
+                let chunk = compile(&ast);
+                let mut machine = Vm::new();
+                machine.set_overflow_mode(overflow);
+                machine.set_float_division_mode(float_division);
+                machine.set_integer_division_mode(integer_division);
+                if let Err(e) = machine.run(&chunk) {
+                    let diagnostic = machine.diagnose(&e);
+                    eprint!("{}", diagnostic.render(&content, &file, color));
+                    process::exit(1);
+                }
+                machine.variables()
+            } else {
+                let mut interpreter = Interpreter::new();
+                interpreter.set_overflow_mode(overflow);
+                interpreter.set_float_division_mode(float_division);
+                interpreter.set_integer_division_mode(integer_division);
+                if let Err(e) = interpreter.eval(&ast) {
+                    let diagnostic = interpreter.diagnose(&e);
+                    eprint!("{}", diagnostic.render(&content, &file, color));
+                    process::exit(1);
+                }
+                interpreter.variables
+            };
 
             println!("\nExecution completed.");
-            println!("Variables: {:?}", interpreter.variables);
+            match decimals {
+                Some(places) => {
+                    let mut names: Vec<_> = variables.iter().collect();
+                    names.sort_by(|a, b| a.0.cmp(b.0));
+                    println!("Variables:");
+                    for (name, value) in names {
+                        println!("  {} = {}", name, value.to_decimal_string(places));
+                    }
+                }
+                None => println!("Variables: {:?}", variables),
+            }
+        }
+        Commands::Repl => {
+            run_repl(color)?;
         }
         Commands::ParserHelp => {
             print_help();
@@ -59,28 +217,113 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn run_repl(color: ColorMode) -> Result<(), Box<dyn std::error::Error>> {
+    let mut editor = DefaultEditor::new()?;
+    let history =
+        std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".tiny_lang_history"));
+    if let Some(path) = &history {
+        //A missing history file just means this is the first session
+        let _ = editor.load_history(path);
+    }
+
+    println!("Tiny Language REPL. Type :help for help, :quit to exit.");
+    let mut repl = Repl::new(color);
+    loop {
+        let prompt = if repl.is_continuing() { "... " } else { ">>> " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = editor.add_history_entry(line.as_str());
+                }
+                match repl.handle_line(&line) {
+                    Reply::Continue => {}
+                    Reply::Output(text) => {
+                        if !text.is_empty() {
+                            println!("{}", text);
+                        }
+                    }
+                    Reply::Error(text) => eprintln!("{}", text.trim_end()),
+                    Reply::Quit => break,
+                }
+            }
+            Err(ReadlineError::Interrupted) => repl.cancel(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
 fn print_help() {
     println!("Tiny Language Parser");
     println!();
     println!("USAGE:");
-    println!("    tiny-lang-parser <COMMAND>");
+    println!("    tiny-lang-parser [--color <auto|always|never>] <COMMAND>");
     println!();
     println!("COMMANDS:");
-    println!("    parse <file>    Parse and execute a Tiny Language file");
+    println!(
+        "    parse <file>    Parse and execute a Tiny Language file (--vm for the bytecode VM,\n                    --overflow checked|wrapping|saturating for integer overflow)"
+    );
+    println!("    repl            Start an interactive session");
     println!("    help            Display this help message");
     println!("    credits         Display credits and authorship information");
     println!();
     println!("Tiny Language Grammar:");
     println!("    program     = {{ statement* }}");
-    println!("    statement   = {{ (assignment | expression) \";\" }}");
-    println!("    assignment  = {{ identifier \"=\" expression }}");
-    println!("    expression  = {{ term (add_op term)* }}");
-    println!("    term        = {{ factor (mul_op factor)* }}");
-    println!("    factor      = {{ number | identifier | \"(\" expression \")\" }}");
+    println!("    statement   = {{ function_def | if_statement | while_statement | for_statement | (assignment | return_statement | print_statement | \"break\" | \"continue\" | expression) \";\" }}");
+    println!("    function_def = {{ \"fn\" identifier \"(\" (identifier (\",\" identifier)*)? \")\" block }}");
+    println!("    return_statement = {{ \"return\" expression? }}");
+    println!("    print_statement = {{ \"print\" (expression (\",\" expression)*)? }}");
+    println!("    if_statement = {{ \"if\" \"(\" expression \")\" block (\"else\" (if_statement | block))? }}");
+    println!("    while_statement = {{ \"while\" \"(\" expression \")\" block }}");
+    println!("    for_statement = {{ \"for\" identifier \"in\" (expression (\"..\" | \"..=\") expression | expression) block }}");
+    println!("    block       = {{ \"{{\" statement* \"}}\" }}");
+    println!(
+        "    assignment  = {{ identifier (\"[\" expression \"]\" | \".\" identifier)* \"=\" expression }}"
+    );
+    println!("    expression  = {{ conjunction (\"||\" conjunction)* }}");
+    println!("    conjunction = {{ bit_or (\"&&\" bit_or)* }}");
+    println!("    bit_or      = {{ bit_xor (\"|\" bit_xor)* }}");
+    println!("    bit_xor     = {{ bit_and (\"^\" bit_and)* }}");
+    println!("    bit_and     = {{ equality (\"&\" equality)* }}");
+    println!("    equality    = {{ comparison (eq_op comparison)* }}");
+    println!("    comparison  = {{ shift (cmp_op shift)* }}");
+    println!("    shift       = {{ sum (shift_op sum)* }}");
+    println!("    sum         = {{ term (add_op term)* }}");
+    println!("    term        = {{ unary (mul_op unary)* }}");
+    println!("    unary       = {{ (\"!\" | \"-\" | \"+\" | \"~\") unary | power }}");
+    println!("    power       = {{ postfix (\"**\" unary)? }}");
+    println!("    postfix     = {{ factor (\"[\" (expression? \":\" expression? | expression) \"]\" | \".\" identifier)* }}");
+    println!(
+        "    factor      = {{ array | map | string | boolean | float | number | call | identifier | \"(\" expression \")\" }}"
+    );
+    println!("    array       = {{ \"[\" (expression (\",\" expression)*)? \"]\" }}");
+    println!("    map         = {{ \"{{\" (map_entry (\",\" map_entry)*)? \"}}\" }}");
+    println!("    map_entry   = {{ (identifier | string) \":\" expression }}");
+    println!("    call        = {{ identifier \"(\" (expression (\",\" expression)*)? \")\" }}");
+    println!("    eq_op       = {{ \"==\" | \"!=\" }}");
+    println!("    cmp_op      = {{ \"<=\" | \">=\" | \"<\" | \">\" }}");
+    println!("    shift_op    = {{ \"<<\" | \">>\" }}");
     println!("    add_op      = {{ \"+\" | \"-\" }}");
-    println!("    mul_op      = {{ \"*\" | \"/\" }}");
-    println!("    number      = {{ ASCII_DIGIT+ }}");
-    println!("    identifier  = {{ ASCII_ALPHA_LOWER+ }}");
+    println!("    mul_op      = {{ \"*\" | \"/\" | \"%\" }}");
+    println!(
+        "    string      = {{ \"\\\"\" (\"{{\" expression \"}}\" | \"\\\\\" ANY | !(\"\\\"\" | \"{{\") ANY)* \"\\\"\" }}"
+    );
+    println!("    boolean     = {{ \"true\" | \"false\" }}");
+    println!("    float       = {{ digits? \".\" digits exponent? | digits exponent }}");
+    println!("    exponent    = {{ (\"e\" | \"E\") (\"+\" | \"-\")? digits }}");
+    println!(
+        "    number      = {{ (\"0x\" | \"0o\" | \"0b\") (ASCII_ALPHANUMERIC | \"_\")* | digits }}"
+    );
+    println!("    digits      = {{ ASCII_DIGIT (ASCII_DIGIT | \"_\")* }}");
+    println!("    identifier  = {{ (XID_START | \"_\") XID_CONTINUE* }}, except reserved keywords");
+    println!(
+        "    COMMENT     = {{ \"/*\" (COMMENT | !\"*/\" ANY)* \"*/\" | \"//\" (!NEWLINE ANY)* }}"
+    );
 }
 
 fn print_credits() {
@@ -92,7 +335,12 @@ fn print_credits() {
     println!("  - Parser for a simple language with variables and arithmetic");
     println!("  - AST generation");
     println!("  - Interpreter with variable storage");
-    println!("  - Error handling");
+    println!("  - User-defined functions with local scopes");
+    println!("  - Print statement with pluggable output sinks");
+    println!("  - Host function registry with a small standard library");
+    println!("  - Bytecode compiler and stack-based VM");
+    println!("  - Interactive REPL with history");
+    println!("  - Error handling with annotated source diagnostics");
     println!("  - Unit test coverage");
     println!();
     println!("Built with:");
@@ -1,17 +1,160 @@
+use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 use pest_derive::Parser;
-use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, Range};
 use thiserror::Error;
 
 #[derive(Parser)]
 #[grammar = "tiny_lang.pest"]
 pub struct TinyLangParser;
 
+/// Location of a piece of source text
+///
+/// Byte offsets index into the original input, while `line` and `column`
+/// are 1-based and describe where the span starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    /// Byte offset of the first character
+    pub start: usize,
+    /// Byte offset one past the last character
+    pub end: usize,
+    /// Line on which the span starts
+    pub line: usize,
+    /// Column on which the span starts
+    pub column: usize,
+}
+
+impl Span {
+    /// Creates a span covering the given Pest pair
+    fn from_pair(pair: &Pair<Rule>) -> Self {
+        let span = pair.as_span();
+        let (line, column) = pair.line_col();
+        Self {
+            start: span.start(),
+            end: span.end(),
+            line,
+            column,
+        }
+    }
+
+    /// Returns the span of `text[range]`, where `text` is the source text
+    /// `self` covers
+    fn slice(self, text: &str, range: Range<usize>) -> Self {
+        let before = &text[..range.start];
+        let (line, column) = match before.rfind('\n') {
+            Some(newline) => (
+                self.line + before.matches('\n').count(),
+                before[newline + 1..].chars().count() + 1,
+            ),
+            None => (self.line, self.column + before.chars().count()),
+        };
+        Self {
+            start: self.start + range.start,
+            end: self.start + range.end,
+            line,
+            column,
+        }
+    }
+
+    /// Returns a span that starts at `self` and ends where `other` ends
+    pub fn to(self, other: Span) -> Self {
+        Self {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A value paired with the source span it was parsed from
+///
+/// Equality only compares the wrapped values, so trees parsed from
+/// differently formatted sources compare equal when their structure matches.
+#[derive(Clone)]
+pub struct Spanned<T> {
+    /// The wrapped value
+    pub node: T,
+    /// Where the value appears in the source
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    /// Wraps `node` together with its source span
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Spanned<T> {
+    /// Formats the wrapped value followed by its position, e.g. `Number(1) @ 1:5`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{:#?} @ {}", self.node, self.span)
+        } else {
+            write!(f, "{:?} @ {}", self.node, self.span)
+        }
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T> From<T> for Spanned<T> {
+    /// Wraps `node` with an empty span, useful for building trees by hand
+    fn from(node: T) -> Self {
+        Self::new(node, Span::default())
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+impl PartialEq<ASTNode> for Spanned<ASTNode> {
+    fn eq(&self, other: &ASTNode) -> bool {
+        self.node == *other
+    }
+}
+
+/// Boxed child node of the syntax tree
+pub type Child = Box<Spanned<ASTNode>>;
+
 /// Abstract Syntax Tree nodes representing the parsed program structure
 #[derive(Debug, Clone, PartialEq)]
 pub enum ASTNode {
     /// Represents a numeric literal (e.g., `42`)
     Number(i64),
+    /// Represents a floating-point literal (e.g., `3.14`, `.5` or `1e-3`)
+    Float(f64),
+    /// Represents a numeric literal too large for `Number`
+    #[cfg(feature = "bigint")]
+    BigNumber(num_bigint::BigInt),
+    /// Represents a boolean literal (`true` or `false`)
+    Bool(bool),
+    /// Represents a string literal (e.g., `"hi\n"`), with escapes resolved
+    String(String),
+    /// Represents a string with interpolated expressions (e.g.,
+    /// `"total = {a + b}"`), as literal pieces and expressions whose values
+    /// are formatted and joined in order
+    Interpolation(Vec<Spanned<ASTNode>>),
+    /// Represents an array literal (e.g., `[1, 2, 3]`)
+    Array(Vec<Spanned<ASTNode>>),
+    /// Represents a map literal (e.g., `{ name: 1, size: 2 }`), as key and
+    /// value expressions in the order written; keys evaluate to strings
+    Map(Vec<(Spanned<ASTNode>, Spanned<ASTNode>)>),
     /// Represents a variable identifier (e.g., `x`)
     Identifier(String),
     /// Represents a variable assignment (e.g., `x = 5`)
@@ -19,16 +162,147 @@ pub enum ASTNode {
         /// The variable name being assigned to
         name: String,
         /// The value being assigned
-        value: Box<ASTNode>,
+        value: Child,
+        /// The `///` doc comment written before the assignment, one line per
+        /// comment line with the markers removed
+        doc: Option<String>,
+    },
+    /// Represents replacing an element of an array or map held in a
+    /// variable (e.g., `xs[i] = 5`, `grid[y][x] = 0` or `obj.size = 2`)
+    IndexAssignment {
+        /// The variable holding the array or map
+        name: String,
+        /// Positions or keys selecting the element, outermost first; a
+        /// field `.name` is the string key `"name"`
+        indexes: Vec<Spanned<ASTNode>>,
+        /// The value being assigned
+        value: Child,
     },
     /// Represents an addition operation (e.g., `a + b`)
-    Add(Box<ASTNode>, Box<ASTNode>),
+    Add(Child, Child),
     /// Represents a subtraction operation (e.g., `a - b`)
-    Sub(Box<ASTNode>, Box<ASTNode>),
+    Sub(Child, Child),
     /// Represents a multiplication operation (e.g., `a * b`)
-    Mul(Box<ASTNode>, Box<ASTNode>),
+    Mul(Child, Child),
     /// Represents a division operation (e.g., `a / b`)
-    Div(Box<ASTNode>, Box<ASTNode>),
+    Div(Child, Child),
+    /// Represents a remainder operation (e.g., `a % b`)
+    Mod(Child, Child),
+    /// Represents an exponentiation (e.g., `a ** b`)
+    Pow(Child, Child),
+    /// Represents a bitwise and (e.g., `a & b`)
+    BitAnd(Child, Child),
+    /// Represents a bitwise or (e.g., `a | b`)
+    BitOr(Child, Child),
+    /// Represents a bitwise exclusive or (e.g., `a ^ b`)
+    BitXor(Child, Child),
+    /// Represents a left shift (e.g., `a << b`)
+    Shl(Child, Child),
+    /// Represents an arithmetic right shift (e.g., `a >> b`)
+    Shr(Child, Child),
+    /// Represents an equality test (e.g., `a == b`)
+    Eq(Child, Child),
+    /// Represents an inequality test (e.g., `a != b`)
+    Ne(Child, Child),
+    /// Represents a less-than comparison (e.g., `a < b`)
+    Lt(Child, Child),
+    /// Represents a less-than-or-equal comparison (e.g., `a <= b`)
+    Le(Child, Child),
+    /// Represents a greater-than comparison (e.g., `a > b`)
+    Gt(Child, Child),
+    /// Represents a greater-than-or-equal comparison (e.g., `a >= b`)
+    Ge(Child, Child),
+    /// Represents a short-circuiting logical and (e.g., `a && b`)
+    And(Child, Child),
+    /// Represents a short-circuiting logical or (e.g., `a || b`)
+    Or(Child, Child),
+    /// Represents a logical negation (e.g., `!a`)
+    Not(Child),
+    /// Represents an arithmetic negation (e.g., `-a`)
+    Neg(Child),
+    /// Represents a unary plus (e.g., `+a`)
+    Plus(Child),
+    /// Represents a bitwise complement (e.g., `~a`)
+    BitNot(Child),
+    /// Represents a conditional statement (e.g., `if (a) { ... } else { ... }`)
+    If {
+        /// The condition deciding which branch runs
+        condition: Child,
+        /// Statements run when the condition is true
+        then_branch: Vec<Spanned<ASTNode>>,
+        /// Statements run when the condition is false; an `else if` chain
+        /// is a single nested `If`
+        else_branch: Option<Vec<Spanned<ASTNode>>>,
+    },
+    /// Represents a loop (e.g., `while (a) { ... }`)
+    While {
+        /// The condition checked before every iteration
+        condition: Child,
+        /// Statements run while the condition is true
+        body: Vec<Spanned<ASTNode>>,
+    },
+    /// Represents a loop over a range or collection (e.g.,
+    /// `for i in 0..10 { ... }` or `for x in xs { ... }`)
+    For {
+        /// The loop variable, which only exists while the loop runs
+        variable: String,
+        /// A [`ASTNode::Range`], or an expression evaluating to the array,
+        /// string or map to iterate over
+        iterable: Child,
+        /// Statements run once per element
+        body: Vec<Spanned<ASTNode>>,
+    },
+    /// Represents the ints from `start` to `end` (e.g., `0..10` or `1..=n`);
+    /// only found as the iterable of a `for` loop
+    Range {
+        /// The first int
+        start: Child,
+        /// The int the range stops at
+        end: Child,
+        /// Whether `end` itself is included (`..=`)
+        inclusive: bool,
+    },
+    /// Represents a function definition (e.g., `fn add(a, b) { ... }`)
+    FunctionDef {
+        /// The function name
+        name: String,
+        /// Parameter names in declaration order
+        params: Vec<String>,
+        /// Statements run by a call
+        body: Vec<Spanned<ASTNode>>,
+    },
+    /// Represents selecting one element (e.g., `s[0]`)
+    Index {
+        /// The value being indexed
+        target: Child,
+        /// Position of the element
+        index: Child,
+    },
+    /// Represents selecting a range of elements (e.g., `s[1:3]` or `s[:2]`)
+    Slice {
+        /// The value being sliced
+        target: Child,
+        /// First position included, the start if left out
+        start: Option<Child>,
+        /// First position excluded, the end if left out
+        end: Option<Child>,
+    },
+    /// Represents a function call (e.g., `add(1, 2)`)
+    Call {
+        /// The name of the called function
+        name: String,
+        /// Argument expressions in call order
+        args: Vec<Spanned<ASTNode>>,
+    },
+    /// Represents leaving the current function (e.g., `return a;`), with an
+    /// optional result
+    Return(Option<Child>),
+    /// Represents writing values as one line of output (e.g., `print x, y;`)
+    Print(Vec<Spanned<ASTNode>>),
+    /// Represents leaving the innermost loop (`break`)
+    Break,
+    /// Represents skipping to the next iteration of the innermost loop (`continue`)
+    Continue,
 }
 
 /// Parser error types
@@ -40,96 +314,45 @@ pub enum ParseError {
     /// Unexpected grammar rule encountered during parsing
     #[error("Unexpected rule: {0:?}")]
     UnexpectedRule(Rule),
-    /// Invalid number format
-    #[error("Invalid number: {0}")]
-    InvalidNumber(String),
+    /// Number literal that is malformed or does not fit its type
+    #[error("Invalid number '{literal}': {reason} at {span}")]
+    InvalidNumber {
+        literal: String,
+        reason: String,
+        span: Span,
+    },
+    /// Malformed escape sequence in a string literal
+    #[error("Invalid escape '{escape}': {reason} at {span}")]
+    InvalidEscape {
+        escape: String,
+        reason: String,
+        span: Span,
+    },
     /// Unexpected end of input
     #[error("Expected {expected:?}, but found end of input")]
     UnexpectedEnd { expected: Rule },
+    /// `break` or `continue` used outside of a loop
+    #[error("'{keyword}' outside of a loop at {span}")]
+    LoopControlOutsideLoop { keyword: &'static str, span: Span },
+    /// `return` used outside of a function body
+    #[error("'return' outside of a function at {span}")]
+    ReturnOutsideFunction { span: Span },
+    /// Function defined inside a block
+    #[error("Function '{name}' must be defined at the top level at {span}")]
+    NestedFunction { name: String, span: Span },
+    /// The same parameter name listed twice
+    #[error("Duplicate parameter '{name}' at {span}")]
+    DuplicateParameter { name: String, span: Span },
+    /// Reserved keyword used as a variable, function or parameter name
+    #[error("'{keyword}' is a reserved keyword and cannot be used as a name at {span}")]
+    ReservedKeyword { keyword: String, span: Span },
 }
 
-/// Interpreter error types
-#[derive(Debug, Error)]
-pub enum EvalError {
-    #[error("Undefined variable '{0}'")]
-    UndefinedVariable(String),
-    #[error("Division by zero")]
-    DivisionByZero,
-    #[error("Runtime error: {0}")]
-    RuntimeError(String),
-}
-
-/// Interpreter that executes the AST and maintains variable state
-#[derive(Debug, Default)]
-pub struct Interpreter {
-    /// HashMap storing variable names and their current values
-    pub variables: HashMap<String, i64>,
-}
-
-impl Interpreter {
-    /// Creates a new interpreter with empty variable state
-    pub fn new() -> Self {
-        Self {
-            variables: HashMap::new(),
-        }
-    }
-
-    /// Evaluates a sequence of AST nodes
-    ///
-    /// # Arguments
-    ///
-    /// * `nodes` - Slice of AST nodes to evaluate
-    ///
-    /// # Errors
-    ///
-    /// Returns `EvalError` if evaluation fails (e.g., undefined variable, division by zero)
-    pub fn eval(&mut self, nodes: &[ASTNode]) -> Result<(), EvalError> {
-        for node in nodes {
-            self.eval_node(node)?;
-        }
-        Ok(())
-    }
-
-    /// Evaluates a single AST node and returns its value
-    fn eval_node(&mut self, node: &ASTNode) -> Result<i64, EvalError> {
-        match node {
-            ASTNode::Number(n) => Ok(*n),
-            ASTNode::Identifier(name) => self
-                .variables
-                .get(name)
-                .copied()
-                .ok_or_else(|| EvalError::UndefinedVariable(name.clone())),
-            ASTNode::Assignment { name, value } => {
-                let val = self.eval_node(value)?;
-                self.variables.insert(name.clone(), val);
-                Ok(val)
-            }
-            ASTNode::Add(l, r) => {
-                let left_val = self.eval_node(l)?;
-                let right_val = self.eval_node(r)?;
-                Ok(left_val + right_val)
-            }
-            ASTNode::Sub(l, r) => {
-                let left_val = self.eval_node(l)?;
-                let right_val = self.eval_node(r)?;
-                Ok(left_val - right_val)
-            }
-            ASTNode::Mul(l, r) => {
-                let left_val = self.eval_node(l)?;
-                let right_val = self.eval_node(r)?;
-                Ok(left_val * right_val)
-            }
-            ASTNode::Div(l, r) => {
-                let left_val = self.eval_node(l)?;
-                let right_val = self.eval_node(r)?;
-                if right_val == 0 {
-                    return Err(EvalError::DivisionByZero);
-                }
-                Ok(left_val / right_val)
-            }
-        }
-    }
-}
+/// Words reserved by the language, which cannot be used as names
+pub const KEYWORDS: &[&str] = &[
+    "break", "continue", "else", "false", "fn", "for", "if", "in", "print", "return", "true",
+    "while",
+];
 
 /// Parses a complete program into a sequence of AST nodes
 ///
@@ -145,21 +368,24 @@ impl Interpreter {
 ///
 /// # Returns
 ///
-/// A vector of AST nodes representing the statements in the program
+/// A vector of spanned AST nodes representing the statements in the program
 ///
 /// # Errors
 ///
 /// Returns `ParseError` if the input doesn't conform to the grammar
-pub fn parse_program(input: &str) -> Result<Vec<ASTNode>, ParseError> {
+pub fn parse_program(input: &str) -> Result<Vec<Spanned<ASTNode>>, ParseError> {
     let pairs = TinyLangParser::parse(Rule::program, input)
         .map_err(|e| ParseError::PestError(Box::new(e)))?;
     let mut nodes = Vec::new();
 
     for pair in pairs {
         if pair.as_rule() == Rule::program {
-            for inner_pair in pair.into_inner() {
+            let start = pair.as_span().start();
+            for (inner_pair, trivia) in with_trivia(pair.into_inner(), start) {
                 if inner_pair.as_rule() == Rule::statement {
-                    nodes.push(parse_statement(inner_pair)?);
+                    let node = parse_statement(inner_pair, trivia)?;
+                    validate_statement(&node, StatementContext::default())?;
+                    nodes.push(node);
                 }
             }
         }
@@ -168,6 +394,164 @@ pub fn parse_program(input: &str) -> Result<Vec<ASTNode>, ParseError> {
     Ok(nodes)
 }
 
+/// Parses a program, recovering from broken statements
+///
+/// # Grammar Rule: recovering_program
+///
+/// Works like [`parse_program`], but when a statement does not match the
+/// grammar the parser skips ahead to the next `;` and carries on. Every
+/// broken statement produces its own error, so a single run reports all
+/// of them instead of stopping at the first.
+///
+/// # Arguments
+///
+/// * `input` - The source code to parse
+///
+/// # Returns
+///
+/// The statements that parsed successfully, in source order, together with
+/// an error for each statement that did not. The error list is empty when
+/// the whole program is valid.
+pub fn parse_program_recovering(input: &str) -> (Vec<Spanned<ASTNode>>, Vec<ParseError>) {
+    let mut nodes = Vec::new();
+    let mut errors = Vec::new();
+
+    let pairs = match TinyLangParser::parse(Rule::recovering_program, input) {
+        Ok(pairs) => pairs,
+        Err(e) => return (nodes, vec![ParseError::PestError(Box::new(e))]),
+    };
+
+    let pairs = pairs.flat_map(|pair| {
+        let start = pair.as_span().start();
+        with_trivia(pair.into_inner(), start)
+    });
+    for (pair, trivia) in pairs {
+        match pair.as_rule() {
+            Rule::statement => match parse_statement(pair, trivia).and_then(|node| {
+                validate_statement(&node, StatementContext::default())?;
+                Ok(node)
+            }) {
+                Ok(node) => nodes.push(node),
+                Err(e) => errors.push(e),
+            },
+            Rule::invalid_statement => errors.push(diagnose_invalid_statement(input, &pair)),
+            _ => {}
+        }
+    }
+
+    (nodes, errors)
+}
+
+/// Explains why the text of an `invalid_statement` is not a statement
+///
+/// The text is reparsed on its own to obtain Pest's description of the
+/// failure, whose position is then moved back into the full input.
+fn diagnose_invalid_statement(input: &str, pair: &Pair<Rule>) -> ParseError {
+    let offset = pair.as_span().start();
+    let position = |pos: usize| {
+        pest::Position::new(input, offset + pos).unwrap_or_else(|| pair.as_span().start_pos())
+    };
+
+    let error = match TinyLangParser::parse(Rule::single_statement, pair.as_str()) {
+        Err(e) => match e.location {
+            pest::error::InputLocation::Pos(pos) => {
+                pest::error::Error::new_from_pos(e.variant, position(pos))
+            }
+            pest::error::InputLocation::Span((start, end)) => {
+                pest::error::Error::new_from_span(e.variant, position(start).span(&position(end)))
+            }
+        },
+        Ok(_) => pest::error::Error::new_from_span(
+            pest::error::ErrorVariant::ParsingError {
+                positives: vec![Rule::statement],
+                negatives: vec![],
+            },
+            pair.as_span(),
+        ),
+    };
+
+    ParseError::PestError(Box::new(error))
+}
+
+/// Where a statement appears, used to reject misplaced statements
+#[derive(Debug, Clone, Copy, Default)]
+struct StatementContext {
+    /// Whether the statement is inside a block rather than at the top level
+    in_block: bool,
+    /// Whether the statement is inside a loop body
+    in_loop: bool,
+    /// Whether the statement is inside a function body
+    in_function: bool,
+}
+
+/// Checks that statements only appear where they are allowed
+///
+/// The grammar accepts any statement anywhere a statement may appear; this
+/// pass rejects `break` and `continue` outside of loop bodies, `return`
+/// outside of function bodies and function definitions that are not at the
+/// top level, so the mistake is reported before the program runs.
+///
+/// # Arguments
+///
+/// * `node` - The statement to check, including any nested blocks
+/// * `context` - Where the statement appears
+fn validate_statement(
+    node: &Spanned<ASTNode>,
+    context: StatementContext,
+) -> Result<(), ParseError> {
+    let validate_block = |block: &[Spanned<ASTNode>], context| {
+        block
+            .iter()
+            .try_for_each(|statement| validate_statement(statement, context))
+    };
+    let nested = StatementContext {
+        in_block: true,
+        ..context
+    };
+
+    match &node.node {
+        ASTNode::Break if !context.in_loop => Err(ParseError::LoopControlOutsideLoop {
+            keyword: "break",
+            span: node.span,
+        }),
+        ASTNode::Continue if !context.in_loop => Err(ParseError::LoopControlOutsideLoop {
+            keyword: "continue",
+            span: node.span,
+        }),
+        ASTNode::Return(_) if !context.in_function => {
+            Err(ParseError::ReturnOutsideFunction { span: node.span })
+        }
+        ASTNode::FunctionDef { name, .. } if context.in_block => Err(ParseError::NestedFunction {
+            name: name.clone(),
+            span: node.span,
+        }),
+        ASTNode::FunctionDef { body, .. } => validate_block(
+            body,
+            StatementContext {
+                in_block: true,
+                in_loop: false,
+                in_function: true,
+            },
+        ),
+        ASTNode::If {
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            validate_block(then_branch, nested)?;
+            validate_block(else_branch.as_deref().unwrap_or_default(), nested)
+        }
+        ASTNode::While { body, .. } | ASTNode::For { body, .. } => validate_block(
+            body,
+            StatementContext {
+                in_loop: true,
+                ..nested
+            },
+        ),
+        _ => Ok(()),
+    }
+}
+
 /// Parses a single statement
 ///
 /// # Grammar Rule: statement  
@@ -175,71 +559,474 @@ pub fn parse_program(input: &str) -> Result<Vec<ASTNode>, ParseError> {
 /// A statement is either an assignment or an expression followed by a semicolon.
 /// This rule defines the basic units of execution in the language.
 ///
+/// Doc comments on the lines right above an assignment are attached to it;
+/// above any other statement they are dropped.
+///
 /// # Arguments
 ///
 /// * `pair` - The Pest parse tree pair for the statement
+/// * `trivia` - The whitespace and comments right before the statement
 ///
 /// # Returns
 ///
 /// An AST node representing the statement
-fn parse_statement(pair: pest::iterators::Pair<Rule>) -> Result<ASTNode, ParseError> {
-    let mut inner = pair.into_inner();
-    let stmt = inner.next().ok_or(ParseError::UnexpectedEnd {
+fn parse_statement(pair: Pair<Rule>, trivia: &str) -> Result<Spanned<ASTNode>, ParseError> {
+    let doc = doc_comment_before(trivia);
+    let stmt = pair.into_inner().next().ok_or(ParseError::UnexpectedEnd {
         expected: Rule::statement,
     })?;
 
     match stmt.as_rule() {
-        Rule::assignment => parse_assignment(stmt),
+        Rule::function_def => parse_function_def(stmt),
+        Rule::return_statement => parse_return_statement(stmt),
+        Rule::print_statement => parse_print_statement(stmt),
+        Rule::if_statement => parse_if_statement(stmt),
+        Rule::while_statement => parse_while_statement(stmt),
+        Rule::for_statement => parse_for_statement(stmt),
+        Rule::break_statement => Ok(Spanned::new(ASTNode::Break, Span::from_pair(&stmt))),
+        Rule::continue_statement => Ok(Spanned::new(ASTNode::Continue, Span::from_pair(&stmt))),
+        Rule::assignment => parse_assignment(stmt, doc),
         Rule::expression => parse_expression(stmt),
         rule => Err(ParseError::UnexpectedRule(rule)),
     }
 }
 
+/// Pairs each of `pairs` with the whitespace and comments between it and
+/// the pair before it, the first starting at `start`
+fn with_trivia<'i>(
+    pairs: Pairs<'i, Rule>,
+    start: usize,
+) -> impl Iterator<Item = (Pair<'i, Rule>, &'i str)> {
+    pairs.scan(start, |end, pair| {
+        let span = pair.as_span();
+        let trivia = &span.get_input()[*end..span.start()];
+        *end = span.end();
+        Some((pair, trivia))
+    })
+}
+
+/// Collects the `///` doc comment lines written right above a statement
+///
+/// The grammar skips doc comments like any other comment, so `trivia`, the
+/// text between the statement and the token before it, is split up again
+/// with the `trivia` rule. The doc comment is the run of doc comment lines
+/// on the lines right above the statement, which must begin its own line;
+/// a blank line or another comment ends the run. Returns `None` when there
+/// are no such lines.
+fn doc_comment_before(trivia: &str) -> Option<String> {
+    let pairs = TinyLangParser::parse(Rule::trivia, trivia).ok()?;
+    let mut doc_lines = Vec::new();
+    let mut line_breaks = 0;
+    for pair in pairs.flatten() {
+        match pair.as_rule() {
+            Rule::doc_comment => {
+                if line_breaks > 1 {
+                    doc_lines.clear();
+                }
+                doc_lines.push(parse_doc_comment(&pair));
+                line_breaks = 0;
+            }
+            Rule::line_break => line_breaks += 1,
+            Rule::other_comment => doc_lines.clear(),
+            _ => {}
+        }
+    }
+    (line_breaks == 1 && !doc_lines.is_empty()).then(|| doc_lines.join("\n"))
+}
+
+/// Extracts the text of a `///` doc comment, dropping the marker and the
+/// single space conventionally written after it
+fn parse_doc_comment(pair: &Pair<Rule>) -> String {
+    let text = &pair.as_str()["///".len()..];
+    text.strip_prefix(' ')
+        .unwrap_or(text)
+        .trim_end()
+        .to_string()
+}
+
+/// Parses a function definition
+///
+/// # Grammar Rule: function_def
+///
+/// A function definition consists of the `fn` keyword, the function name,
+/// a parenthesized list of parameter names and a block forming the body.
+/// Every parameter name must be distinct.
+///
+/// Format: `"fn" identifier "(" (identifier ("," identifier)*)? ")" block`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the function definition
+///
+/// # Returns
+///
+/// An AST node representing the function definition
+fn parse_function_def(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let mut inner = pair.into_inner().skip(1);
+
+    let name_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::identifier,
+    })?;
+    let name = parse_identifier(&name_pair)?;
+
+    let mut params: Vec<String> = Vec::new();
+    for param_pair in inner.by_ref() {
+        match param_pair.as_rule() {
+            Rule::identifier => {
+                let param = parse_identifier(&param_pair)?;
+                if params.contains(&param) {
+                    return Err(ParseError::DuplicateParameter {
+                        name: param,
+                        span: Span::from_pair(&param_pair),
+                    });
+                }
+                params.push(param);
+            }
+            Rule::block => {
+                return Ok(Spanned::new(
+                    ASTNode::FunctionDef {
+                        name,
+                        params,
+                        body: parse_block(param_pair)?,
+                    },
+                    span,
+                ))
+            }
+            rule => return Err(ParseError::UnexpectedRule(rule)),
+        }
+    }
+
+    Err(ParseError::UnexpectedEnd {
+        expected: Rule::block,
+    })
+}
+
+/// Parses a return statement
+///
+/// # Grammar Rule: return_statement
+///
+/// A return statement consists of the `return` keyword and an optional
+/// expression giving the result of the call.
+///
+/// Format: `"return" expression?`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the return statement
+///
+/// # Returns
+///
+/// An AST node representing the return statement
+fn parse_return_statement(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let value = match pair.into_inner().nth(1) {
+        Some(expr_pair) => Some(Box::new(parse_expression(expr_pair)?)),
+        None => None,
+    };
+    Ok(Spanned::new(ASTNode::Return(value), span))
+}
+
+/// Parses a print statement
+///
+/// # Grammar Rule: print_statement
+///
+/// A print statement consists of the `print` keyword and a possibly empty,
+/// comma separated list of expressions whose values form one output line.
+///
+/// Format: `"print" (expression ("," expression)*)?`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the print statement
+///
+/// # Returns
+///
+/// An AST node representing the print statement
+fn parse_print_statement(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let values = pair
+        .into_inner()
+        .skip(1)
+        .map(parse_expression)
+        .collect::<Result<_, _>>()?;
+    Ok(Spanned::new(ASTNode::Print(values), span))
+}
+
+/// Parses a conditional statement
+///
+/// # Grammar Rule: if_statement
+///
+/// A conditional consists of a parenthesized condition, a block run when
+/// the condition holds, and an optional `else` part. The `else` part is
+/// either a block or another conditional, which allows `else if` chains.
+///
+/// Format: `"if" "(" expression ")" block ("else" (if_statement | block))?`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the conditional
+///
+/// # Returns
+///
+/// An AST node representing the conditional
+fn parse_if_statement(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let mut inner = pair
+        .into_inner()
+        .filter(|p| !matches!(p.as_rule(), Rule::if_keyword | Rule::else_keyword));
+
+    let condition_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::expression,
+    })?;
+    let condition = parse_expression(condition_pair)?;
+
+    let then_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::block,
+    })?;
+    let then_branch = parse_block(then_pair)?;
+
+    let else_branch = match inner.next() {
+        None => None,
+        Some(else_pair) => match else_pair.as_rule() {
+            Rule::block => Some(parse_block(else_pair)?),
+            Rule::if_statement => Some(vec![parse_if_statement(else_pair)?]),
+            rule => return Err(ParseError::UnexpectedRule(rule)),
+        },
+    };
+
+    Ok(Spanned::new(
+        ASTNode::If {
+            condition: Box::new(condition),
+            then_branch,
+            else_branch,
+        },
+        span,
+    ))
+}
+
+/// Parses a loop
+///
+/// # Grammar Rule: while_statement
+///
+/// A loop consists of a parenthesized condition and a block that runs
+/// for as long as the condition holds. `break` and `continue` inside the
+/// block affect the innermost enclosing loop.
+///
+/// Format: `"while" "(" expression ")" block`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the loop
+///
+/// # Returns
+///
+/// An AST node representing the loop
+fn parse_while_statement(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let mut inner = pair.into_inner().skip(1);
+
+    let condition_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::expression,
+    })?;
+    let condition = parse_expression(condition_pair)?;
+
+    let body_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::block,
+    })?;
+    let body = parse_block(body_pair)?;
+
+    Ok(Spanned::new(
+        ASTNode::While {
+            condition: Box::new(condition),
+            body,
+        },
+        span,
+    ))
+}
+
+/// Parses a loop over a range or collection
+///
+/// # Grammar Rule: for_statement
+///
+/// A for loop names a variable, then what it iterates over: a range of
+/// ints, half-open with `..` or inclusive with `..=`, or an expression
+/// giving an array, string or map. The block runs once per element with
+/// the variable set to it; `break` and `continue` work as in `while` loops.
+///
+/// Format: `"for" identifier "in" (expression (".." | "..=") expression | expression) block`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the loop
+///
+/// # Returns
+///
+/// An AST node representing the loop
+fn parse_for_statement(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let mut inner = pair
+        .into_inner()
+        .filter(|p| !matches!(p.as_rule(), Rule::for_keyword | Rule::in_keyword));
+
+    let variable_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::identifier,
+    })?;
+    let variable = parse_identifier(&variable_pair)?;
+
+    let iterable_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::expression,
+    })?;
+    let iterable = match iterable_pair.as_rule() {
+        Rule::range => parse_range(iterable_pair)?,
+        _ => parse_expression(iterable_pair)?,
+    };
+
+    let body_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::block,
+    })?;
+    let body = parse_block(body_pair)?;
+
+    Ok(Spanned::new(
+        ASTNode::For {
+            variable,
+            iterable: Box::new(iterable),
+            body,
+        },
+        span,
+    ))
+}
+
+/// Parses the range of a for loop
+///
+/// # Grammar Rule: range
+///
+/// Format: `expression (".." | "..=") expression`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the range
+///
+/// # Returns
+///
+/// An AST node representing the range
+fn parse_range(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let mut inner = pair.into_inner();
+
+    let start_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::expression,
+    })?;
+    let start = parse_expression(start_pair)?;
+    let op_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::range_op,
+    })?;
+    let end_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::expression,
+    })?;
+    let end = parse_expression(end_pair)?;
+
+    Ok(Spanned::new(
+        ASTNode::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive: op_pair.as_str() == "..=",
+        },
+        span,
+    ))
+}
+
+/// Parses a block of statements
+///
+/// # Grammar Rule: block
+///
+/// A block is a brace-delimited list of statements. Blocks do not introduce
+/// a new scope; assignments inside them update the surrounding variables.
+///
+/// Format: `"{" statement* "}"`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the block
+///
+/// # Returns
+///
+/// The statements inside the block
+fn parse_block(pair: Pair<Rule>) -> Result<Vec<Spanned<ASTNode>>, ParseError> {
+    //The statements follow the opening brace
+    let start = pair.as_span().start() + 1;
+    with_trivia(pair.into_inner(), start)
+        .map(|(statement, trivia)| parse_statement(statement, trivia))
+        .collect()
+}
+
 /// Parses a variable assignment
 ///
 /// # Grammar Rule: assignment
 ///
 /// An assignment consists of an identifier followed by an equals sign and
 /// an expression. It creates or updates a variable in the interpreter's
-/// environment.
+/// environment. Indexes or fields after the identifier replace an element
+/// of the array or map held in the variable instead; the doc comment is
+/// dropped then.
 ///
-/// Format: `identifier = expression`
+/// Format: `identifier ("[" expression "]" | "." identifier)* = expression`
 ///
 /// # Arguments
 ///
 /// * `pair` - The Pest parse tree pair for the assignment
+/// * `doc` - The doc comment written before the statement, if any
 ///
 /// # Returns
 ///
 /// An AST node representing the assignment
-fn parse_assignment(pair: pest::iterators::Pair<Rule>) -> Result<ASTNode, ParseError> {
+fn parse_assignment(pair: Pair<Rule>, doc: Option<String>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
     let mut inner = pair.into_inner();
 
     let name_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
         expected: Rule::identifier,
     })?;
-    let name = name_pair.as_str().to_string();
+    let name = parse_identifier(&name_pair)?;
 
-    let expr_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
-        expected: Rule::expression,
-    })?;
-    let value = parse_expression(expr_pair)?;
+    let mut indexes = Vec::new();
+    let expr_pair = loop {
+        let pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+            expected: Rule::expression,
+        })?;
+        match pair.as_rule() {
+            Rule::target_field => indexes.push(parse_field_name(pair)?),
+            Rule::target_index => {
+                let index_pair = pair.into_inner().next().ok_or(ParseError::UnexpectedEnd {
+                    expected: Rule::expression,
+                })?;
+                indexes.push(parse_expression(index_pair)?);
+            }
+            _ => break pair,
+        }
+    };
+    let value = Box::new(parse_expression(expr_pair)?);
 
-    Ok(ASTNode::Assignment {
-        name,
-        value: Box::new(value),
-    })
+    let node = if indexes.is_empty() {
+        ASTNode::Assignment { name, value, doc }
+    } else {
+        ASTNode::IndexAssignment {
+            name,
+            indexes,
+            value,
+        }
+    };
+    Ok(Spanned::new(node, span))
 }
 
-/// Parses an expression with addition and subtraction operations
+/// Parses an expression with logical or operations
 ///
 /// # Grammar Rule: expression
 ///
-/// An expression consists of terms separated by addition or subtraction
-/// operators. This rule handles operator precedence where addition and
-/// subtraction have lower precedence than multiplication and division.
+/// An expression consists of conjunctions separated by `||`. Logical or
+/// has the lowest precedence of all operators.
 ///
-/// Format: `term (add_op term)*`
+/// Format: `conjunction (or_op conjunction)*`
 ///
 /// # Arguments
 ///
@@ -248,59 +1035,113 @@ fn parse_assignment(pair: pest::iterators::Pair<Rule>) -> Result<ASTNode, ParseE
 /// # Returns
 ///
 /// An AST node representing the expression
-fn parse_expression(pair: pest::iterators::Pair<Rule>) -> Result<ASTNode, ParseError> {
-    let mut pairs: Vec<_> = pair.into_inner().collect();
+fn parse_expression(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    parse_binary_chain(pair, Rule::conjunction, parse_conjunction)
+}
 
-    if pairs.is_empty() {
-        return Err(ParseError::UnexpectedEnd {
-            expected: Rule::term,
-        });
-    }
+/// Parses a conjunction with logical and operations
+///
+/// # Grammar Rule: conjunction
+///
+/// A conjunction consists of bitwise ors separated by `&&`, which binds
+/// tighter than `||` but looser than any bitwise operator.
+///
+/// Format: `bit_or (and_op bit_or)*`
+fn parse_conjunction(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    parse_binary_chain(pair, Rule::bit_or, parse_bit_or)
+}
 
-    let mut current_node = parse_term(pairs.remove(0))?;
+/// Parses a bitwise or
+///
+/// # Grammar Rule: bit_or
+///
+/// A bitwise or consists of bitwise xors separated by `|`.
+///
+/// Format: `bit_xor (bit_or_op bit_xor)*`
+fn parse_bit_or(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    parse_binary_chain(pair, Rule::bit_xor, parse_bit_xor)
+}
 
-    // Process pairs in chunks of 2: (operator, term)
-    let mut i = 0;
-    while i < pairs.len() {
-        if i + 1 >= pairs.len() {
-            return Err(ParseError::UnexpectedEnd {
-                expected: Rule::term,
-            });
-        }
+/// Parses a bitwise xor
+///
+/// # Grammar Rule: bit_xor
+///
+/// A bitwise xor consists of bitwise ands separated by `^`.
+///
+/// Format: `bit_and (bit_xor_op bit_and)*`
+fn parse_bit_xor(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    parse_binary_chain(pair, Rule::bit_and, parse_bit_and)
+}
 
-        let op_pair = &pairs[i];
-        let term_pair = &pairs[i + 1];
-
-        current_node = match op_pair.as_rule() {
-            Rule::add_op => match op_pair.as_str() {
-                "+" => ASTNode::Add(
-                    Box::new(current_node),
-                    Box::new(parse_term(term_pair.clone())?),
-                ),
-                "-" => ASTNode::Sub(
-                    Box::new(current_node),
-                    Box::new(parse_term(term_pair.clone())?),
-                ),
-                _ => return Err(ParseError::UnexpectedRule(op_pair.as_rule())),
-            },
-            _ => return Err(ParseError::UnexpectedRule(op_pair.as_rule())),
-        };
+/// Parses a bitwise and
+///
+/// # Grammar Rule: bit_and
+///
+/// A bitwise and consists of equalities separated by `&`. As in C, the
+/// bitwise operators bind looser than comparisons, so masks are usually
+/// parenthesized: `(flags & mask) == mask`.
+///
+/// Format: `equality (bit_and_op equality)*`
+fn parse_bit_and(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    parse_binary_chain(pair, Rule::equality, parse_equality)
+}
 
-        i += 2;
-    }
+/// Parses an equality test
+///
+/// # Grammar Rule: equality
+///
+/// An equality consists of comparisons separated by `==` or `!=`.
+///
+/// Format: `comparison (eq_op comparison)*`
+fn parse_equality(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    parse_binary_chain(pair, Rule::comparison, parse_comparison)
+}
 
-    Ok(current_node)
+/// Parses an ordering comparison
+///
+/// # Grammar Rule: comparison
+///
+/// A comparison consists of shifts separated by `<`, `<=`, `>` or `>=`.
+///
+/// Format: `shift (cmp_op shift)*`
+fn parse_comparison(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    parse_binary_chain(pair, Rule::shift, parse_shift)
 }
 
-/// Parses a term with multiplication and division operations  
+/// Parses a shift
+///
+/// # Grammar Rule: shift
+///
+/// A shift consists of sums separated by `<<` or `>>`, so `1 << n - 1`
+/// shifts by `n - 1`.
+///
+/// Format: `sum (shift_op sum)*`
+fn parse_shift(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    parse_binary_chain(pair, Rule::sum, parse_sum)
+}
+
+/// Parses a sum with addition and subtraction operations
+///
+/// # Grammar Rule: sum
+///
+/// A sum consists of terms separated by addition or subtraction
+/// operators. This rule handles operator precedence where addition and
+/// subtraction have lower precedence than multiplication and division.
+///
+/// Format: `term (add_op term)*`
+fn parse_sum(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    parse_binary_chain(pair, Rule::term, parse_term)
+}
+
+/// Parses a term with multiplication, division and remainder operations
 ///
 /// # Grammar Rule: term
 ///
-/// A term consists of factors separated by multiplication or division
-/// operators. This rule handles the higher precedence of multiplication
-/// and division over addition and subtraction.
+/// A term consists of unary expressions separated by multiplication,
+/// division or remainder operators. This rule handles the higher precedence
+/// of multiplication and division over addition and subtraction.
 ///
-/// Format: `factor (mul_op factor)*`
+/// Format: `unary (mul_op unary)*`
 ///
 /// # Arguments
 ///
@@ -309,60 +1150,247 @@ fn parse_expression(pair: pest::iterators::Pair<Rule>) -> Result<ASTNode, ParseE
 /// # Returns
 ///
 /// An AST node representing the term
-fn parse_term(pair: pest::iterators::Pair<Rule>) -> Result<ASTNode, ParseError> {
-    let mut pairs: Vec<_> = pair.into_inner().collect();
+fn parse_term(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    parse_binary_chain(pair, Rule::unary, parse_unary)
+}
 
-    if pairs.is_empty() {
-        return Err(ParseError::UnexpectedEnd {
-            expected: Rule::factor,
-        });
+/// Parses a left-associative chain of binary operations
+///
+/// Shared by every binary precedence level. The pair's children alternate
+/// between operands and operators: `operand (op operand)*`.
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the chain
+/// * `operand` - The rule of the operands, used in error reports
+/// * `parse_operand` - Parser for a single operand
+fn parse_binary_chain(
+    pair: Pair<Rule>,
+    operand: Rule,
+    parse_operand: fn(Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError>,
+) -> Result<Spanned<ASTNode>, ParseError> {
+    let mut pairs = pair.into_inner();
+
+    let first = pairs
+        .next()
+        .ok_or(ParseError::UnexpectedEnd { expected: operand })?;
+    let mut current_node = parse_operand(first)?;
+
+    // Process pairs in chunks of 2: (operator, operand)
+    while let Some(op_pair) = pairs.next() {
+        let operand_pair = pairs
+            .next()
+            .ok_or(ParseError::UnexpectedEnd { expected: operand })?;
+
+        let right = parse_operand(operand_pair)?;
+        let span = current_node.span.to(right.span);
+        let (l, r) = (Box::new(current_node), Box::new(right));
+        let node = match (op_pair.as_rule(), op_pair.as_str()) {
+            (Rule::or_op, _) => ASTNode::Or(l, r),
+            (Rule::and_op, _) => ASTNode::And(l, r),
+            (Rule::bit_or_op, _) => ASTNode::BitOr(l, r),
+            (Rule::bit_xor_op, _) => ASTNode::BitXor(l, r),
+            (Rule::bit_and_op, _) => ASTNode::BitAnd(l, r),
+            (Rule::eq_op, "==") => ASTNode::Eq(l, r),
+            (Rule::eq_op, "!=") => ASTNode::Ne(l, r),
+            (Rule::cmp_op, "<") => ASTNode::Lt(l, r),
+            (Rule::cmp_op, "<=") => ASTNode::Le(l, r),
+            (Rule::cmp_op, ">") => ASTNode::Gt(l, r),
+            (Rule::cmp_op, ">=") => ASTNode::Ge(l, r),
+            (Rule::shift_op, "<<") => ASTNode::Shl(l, r),
+            (Rule::shift_op, ">>") => ASTNode::Shr(l, r),
+            (Rule::add_op, "+") => ASTNode::Add(l, r),
+            (Rule::add_op, "-") => ASTNode::Sub(l, r),
+            (Rule::mul_op, "*") => ASTNode::Mul(l, r),
+            (Rule::mul_op, "/") => ASTNode::Div(l, r),
+            (Rule::mul_op, "%") => ASTNode::Mod(l, r),
+            (rule, _) => return Err(ParseError::UnexpectedRule(rule)),
+        };
+        current_node = Spanned::new(node, span);
     }
 
-    let mut current_node = parse_factor(pairs.remove(0))?;
+    Ok(current_node)
+}
 
-    // Process pairs in chunks of 2: (operator, factor)
-    let mut i = 0;
-    while i < pairs.len() {
-        if i + 1 >= pairs.len() {
-            return Err(ParseError::UnexpectedEnd {
-                expected: Rule::factor,
-            });
+/// Parses a unary expression
+///
+/// # Grammar Rule: unary
+///
+/// A unary expression is a power preceded by any number of prefix
+/// operators (`!`, `-`, `+` or `~`), which bind tighter than every binary
+/// operator except `**`.
+///
+/// Format: `(not_op | neg_op | plus_op | bit_not_op) unary | power`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the unary expression
+///
+/// # Returns
+///
+/// An AST node representing the unary expression
+fn parse_unary(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let mut inner = pair.into_inner();
+    let first = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::factor,
+    })?;
+
+    let prefix: fn(Child) -> ASTNode = match first.as_rule() {
+        Rule::power => return parse_power(first),
+        Rule::not_op => ASTNode::Not,
+        Rule::neg_op => ASTNode::Neg,
+        Rule::plus_op => ASTNode::Plus,
+        Rule::bit_not_op => ASTNode::BitNot,
+        rule => return Err(ParseError::UnexpectedRule(rule)),
+    };
+    let operand = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::unary,
+    })?;
+    //The magnitude of i64::MIN does not fit in an i64 on its own, so a
+    //negation written directly in front of it forms a single literal
+    if first.as_rule() == Rule::neg_op && is_min_magnitude(&operand) {
+        return Ok(Spanned::new(ASTNode::Number(i64::MIN), span));
+    }
+    Ok(Spanned::new(prefix(Box::new(parse_unary(operand)?)), span))
+}
+
+/// Returns `true` if `unary` is nothing but a number literal equal to
+/// 2^63, the magnitude of `i64::MIN`
+fn is_min_magnitude(unary: &Pair<Rule>) -> bool {
+    //unary > power > postfix > factor > number, each without operators,
+    //indexes or parentheses
+    let mut pair = unary.clone();
+    for rule in [Rule::power, Rule::postfix, Rule::factor, Rule::number] {
+        let mut inner = pair.into_inner();
+        match (inner.next(), inner.next()) {
+            (Some(next), None) if next.as_rule() == rule => pair = next,
+            _ => return false,
         }
+    }
+    number_digits(pair.as_str()).is_ok_and(|(radix, digits)| {
+        u64::from_str_radix(&digits, radix) == Ok(i64::MIN.unsigned_abs())
+    })
+}
+
+/// Parses an exponentiation
+///
+/// # Grammar Rule: power
+///
+/// A power is a postfix expression, optionally followed by `**` and a unary
+/// expression as the exponent. Because the exponent is itself a unary
+/// expression, `2 ** 3 ** 2` groups as `2 ** (3 ** 2)` and `2 ** -1`
+/// needs no parentheses.
+///
+/// Format: `postfix (pow_op unary)?`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the power
+///
+/// # Returns
+///
+/// An AST node representing the power
+fn parse_power(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let mut inner = pair.into_inner();
+    let base = parse_postfix(inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::postfix,
+    })?)?;
+
+    //Skip the pow_op, leaving the exponent
+    match inner.nth(1) {
+        Some(exponent) => Ok(Spanned::new(
+            ASTNode::Pow(Box::new(base), Box::new(parse_unary(exponent)?)),
+            span,
+        )),
+        None => Ok(base),
+    }
+}
 
-        let op_pair = &pairs[i];
-        let factor_pair = &pairs[i + 1];
-
-        current_node = match op_pair.as_rule() {
-            Rule::mul_op => match op_pair.as_str() {
-                "*" => ASTNode::Mul(
-                    Box::new(current_node),
-                    Box::new(parse_factor(factor_pair.clone())?),
-                ),
-                "/" => ASTNode::Div(
-                    Box::new(current_node),
-                    Box::new(parse_factor(factor_pair.clone())?),
-                ),
-                _ => return Err(ParseError::UnexpectedRule(op_pair.as_rule())),
+/// Parses indexing, slicing and field access
+///
+/// # Grammar Rule: postfix
+///
+/// A postfix expression is a factor followed by any number of indexes or
+/// fields, applied left to right, so `s[1:][0]` takes the first character
+/// of the slice. An index holding a colon is a slice whose bounds may be
+/// left out. A field `.name` is an index by the string key `"name"`.
+///
+/// Format: `factor ("[" (expression? ":" expression? | expression) "]" | "." identifier)*`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the postfix expression
+///
+/// # Returns
+///
+/// An AST node representing the indexed value
+fn parse_postfix(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let mut inner = pair.into_inner();
+    let mut node = parse_factor(inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::factor,
+    })?)?;
+
+    for index_pair in inner {
+        let span = node.span.to(Span::from_pair(&index_pair));
+        let target = Box::new(node);
+        if index_pair.as_rule() == Rule::field {
+            let key = parse_field_name(index_pair)?;
+            node = Spanned::new(
+                ASTNode::Index {
+                    target,
+                    index: Box::new(key),
+                },
+                span,
+            );
+            continue;
+        }
+        let selector = index_pair
+            .into_inner()
+            .next()
+            .ok_or(ParseError::UnexpectedEnd {
+                expected: Rule::expression,
+            })?;
+
+        let indexed = match selector.as_rule() {
+            Rule::slice => {
+                let (mut start, mut end) = (None, None);
+                let mut after_colon = false;
+                for bound in selector.into_inner() {
+                    match bound.as_rule() {
+                        Rule::slice_colon => after_colon = true,
+                        _ if after_colon => end = Some(Box::new(parse_expression(bound)?)),
+                        _ => start = Some(Box::new(parse_expression(bound)?)),
+                    }
+                }
+                ASTNode::Slice { target, start, end }
+            }
+            _ => ASTNode::Index {
+                target,
+                index: Box::new(parse_expression(selector)?),
             },
-            _ => return Err(ParseError::UnexpectedRule(op_pair.as_rule())),
         };
-
-        i += 2;
+        node = Spanned::new(indexed, span);
     }
 
-    Ok(current_node)
+    Ok(node)
 }
 
-/// Parses a factor (number, identifier, or parenthesized expression)
+/// Parses a factor (boolean, number, identifier, or parenthesized expression)
 ///
 /// # Grammar Rule: factor
 ///
 /// A factor is the most basic unit in an expression. It can be:
-/// - A numeric literal
-/// - A variable identifier  
+/// - An array literal such as `[1, 2, 3]`
+/// - A map literal such as `{ name: 1, size: 2 }`
+/// - A string literal such as `"total: "`
+/// - A boolean literal (`true` or `false`)
+/// - A numeric literal, either an integer or a float such as `3.14`, `.5` or `1e-3`
+/// - A variable identifier
 /// - A parenthesized expression (for explicit precedence control)
 ///
-/// Format: `number | identifier | "(" expression ")"`
+/// Format: `array | map | string | boolean | float | number | call | identifier | "(" expression ")"`
 ///
 /// # Arguments
 ///
@@ -371,25 +1399,365 @@ fn parse_term(pair: pest::iterators::Pair<Rule>) -> Result<ASTNode, ParseError>
 /// # Returns
 ///
 /// An AST node representing the factor
-fn parse_factor(pair: pest::iterators::Pair<Rule>) -> Result<ASTNode, ParseError> {
+fn parse_factor(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
     let inner = pair.into_inner().next().ok_or(ParseError::UnexpectedEnd {
         expected: Rule::number,
     })?;
 
+    let span = Span::from_pair(&inner);
     match inner.as_rule() {
-        Rule::number => {
-            let num_str = inner.as_str();
-            num_str
-                .parse()
-                .map(ASTNode::Number)
-                .map_err(|_| ParseError::InvalidNumber(num_str.to_string()))
+        Rule::string => parse_string(inner),
+        Rule::boolean => Ok(Spanned::new(ASTNode::Bool(inner.as_str() == "true"), span)),
+        Rule::float => {
+            let literal = inner.as_str();
+            match literal.replace('_', "").parse::<f64>() {
+                Ok(x) if x.is_finite() => Ok(Spanned::new(ASTNode::Float(x), span)),
+                _ => Err(ParseError::InvalidNumber {
+                    literal: literal.to_string(),
+                    reason: "is too large for a float".to_string(),
+                    span,
+                }),
+            }
         }
-        Rule::identifier => Ok(ASTNode::Identifier(inner.as_str().to_string())),
+        Rule::number => parse_number(inner.as_str(), span),
+        Rule::identifier => Ok(Spanned::new(
+            ASTNode::Identifier(parse_identifier(&inner)?),
+            span,
+        )),
+        Rule::call => parse_call(inner),
+        Rule::array => {
+            let elements = inner
+                .into_inner()
+                .map(parse_expression)
+                .collect::<Result<_, _>>()?;
+            Ok(Spanned::new(ASTNode::Array(elements), span))
+        }
+        Rule::map => parse_map(inner),
         Rule::expression => parse_expression(inner),
         rule => Err(ParseError::UnexpectedRule(rule)),
     }
 }
 
+/// Parses a map literal
+///
+/// # Grammar Rule: map
+///
+/// A map is a list of entries in braces. Each key is a name, which stands
+/// for the string of the same text, or a string literal; a key written
+/// twice keeps the value of its last entry.
+///
+/// Format: `"{" (entry ("," entry)*)? "}"` where an entry is
+/// `(identifier | string) ":" expression`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the map
+///
+/// # Returns
+///
+/// An AST node holding the key and value expression of every entry
+fn parse_map(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let mut entries = Vec::new();
+
+    for entry in pair.into_inner() {
+        let mut inner = entry.into_inner();
+        let key_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+            expected: Rule::identifier,
+        })?;
+        let key = match key_pair.as_rule() {
+            Rule::string => parse_string(key_pair)?,
+            _ => Spanned::new(
+                ASTNode::String(parse_identifier(&key_pair)?),
+                Span::from_pair(&key_pair),
+            ),
+        };
+        let value_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+            expected: Rule::expression,
+        })?;
+        entries.push((key, parse_expression(value_pair)?));
+    }
+
+    Ok(Spanned::new(ASTNode::Map(entries), span))
+}
+
+/// Converts the name after a `.` into the string key it selects
+fn parse_field_name(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let name_pair = pair.into_inner().next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::identifier,
+    })?;
+    Ok(Spanned::new(
+        ASTNode::String(parse_identifier(&name_pair)?),
+        Span::from_pair(&name_pair),
+    ))
+}
+
+/// Parses a string literal
+///
+/// # Grammar Rule: string
+///
+/// A string is literal text mixed with interpolations, expressions in
+/// braces whose values are formatted into the string. A string without
+/// interpolations becomes a plain string node; otherwise the literal
+/// pieces and expressions become the parts of an interpolation node.
+///
+/// Format: `"\"" (interpolation | string_text)* "\""`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the string
+///
+/// # Returns
+///
+/// An AST node representing the string
+fn parse_string(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let mut parts = Vec::new();
+    let mut interpolated = false;
+
+    for part in pair.into_inner() {
+        match part.as_rule() {
+            Rule::string_text => {
+                let part_span = Span::from_pair(&part);
+                let text = unescape(part.as_str(), part_span)?;
+                parts.push(Spanned::new(ASTNode::String(text), part_span));
+            }
+            Rule::interpolation => {
+                let expr_pair = part.into_inner().next().ok_or(ParseError::UnexpectedEnd {
+                    expected: Rule::expression,
+                })?;
+                parts.push(parse_expression(expr_pair)?);
+                interpolated = true;
+            }
+            rule => return Err(ParseError::UnexpectedRule(rule)),
+        }
+    }
+
+    let node = if interpolated {
+        ASTNode::Interpolation(parts)
+    } else {
+        //Without interpolations the literal is a single piece of text
+        match parts.pop() {
+            Some(Spanned {
+                node: ASTNode::String(text),
+                ..
+            }) => ASTNode::String(text),
+            _ => ASTNode::String(String::new()),
+        }
+    };
+    Ok(Spanned::new(node, span))
+}
+
+/// Resolves the escape sequences in literal text of a string
+///
+/// # Grammar Rule: string_text
+///
+/// The supported escapes are `\n`, `\t`, `\r`, `\0`, `\\`, `\"`, `\{`,
+/// `\}` and `\u{...}` with one to six hexadecimal digits naming a Unicode
+/// scalar value. Any other escape is an error located at the escape itself.
+///
+/// Format: `("\\" ANY | !("\"" | "{") ANY)+`
+///
+/// # Arguments
+///
+/// * `body` - The text as written in the source
+/// * `span` - Where the text appears in the source
+///
+/// # Returns
+///
+/// The text with every escape replaced by the character it stands for
+fn unescape(body: &str, span: Span) -> Result<String, ParseError> {
+    let mut text = String::with_capacity(body.len());
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((_, c)) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        //The grammar guarantees a character after every backslash
+        let (offset, kind) = chars.next().unwrap_or((body.len(), ' '));
+        let escape_start = offset - 1;
+        let invalid = |end: usize, reason: &str| ParseError::InvalidEscape {
+            escape: body[escape_start..end].to_string(),
+            reason: reason.to_string(),
+            span: span.slice(body, escape_start..end),
+        };
+
+        text.push(match kind {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '{' => '{',
+            '}' => '}',
+            'u' => {
+                let after_u = offset + 1;
+                if chars.next_if(|&(_, c)| c == '{').is_none() {
+                    return Err(invalid(after_u, "expected `{` after `\\u`"));
+                }
+                let mut digits = String::new();
+                let end = loop {
+                    match chars.next() {
+                        Some((i, '}')) if !digits.is_empty() => break i + 1,
+                        Some((_, c)) if c.is_ascii_hexdigit() && digits.len() < 6 => digits.push(c),
+                        Some((i, c)) => {
+                            return Err(invalid(
+                                i + c.len_utf8(),
+                                "expected one to six hexadecimal digits and `}`",
+                            ))
+                        }
+                        None => return Err(invalid(body.len(), "unterminated `\\u{...}` escape")),
+                    }
+                };
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| invalid(end, "not a Unicode scalar value"))?
+            }
+            other => {
+                return Err(invalid(
+                    offset + other.len_utf8(),
+                    "unknown escape sequence",
+                ));
+            }
+        });
+    }
+
+    Ok(text)
+}
+
+/// Converts an integer literal
+///
+/// # Grammar Rule: number
+///
+/// Decimal literals are digits; `0x`, `0o` and `0b` prefixes select
+/// hexadecimal, octal and binary. Underscores may separate digits anywhere
+/// after the first digit or the prefix, e.g. `1_000_000` or `0xFF_FF`.
+///
+/// Format: `"0" ("x" | "o" | "b") (ASCII_ALPHANUMERIC | "_")* | digits`
+///
+/// # Arguments
+///
+/// * `literal` - The literal as written in the source
+/// * `span` - Location of the literal, used in errors
+///
+/// # Returns
+///
+/// A `Number` node, or a `BigNumber` for literals beyond `i64` when the
+/// `bigint` feature is enabled
+fn parse_number(literal: &str, span: Span) -> Result<Spanned<ASTNode>, ParseError> {
+    let invalid = |reason: String| ParseError::InvalidNumber {
+        literal: literal.to_string(),
+        reason,
+        span,
+    };
+    let (radix, digits) = number_digits(literal).map_err(invalid)?;
+
+    if let Ok(n) = i64::from_str_radix(&digits, radix) {
+        return Ok(Spanned::new(ASTNode::Number(n), span));
+    }
+    #[cfg(feature = "bigint")]
+    if let Some(n) = num_bigint::BigInt::parse_bytes(digits.as_bytes(), radix) {
+        return Ok(Spanned::new(ASTNode::BigNumber(n), span));
+    }
+    let max = match radix {
+        16 => format!("0x{:x}", i64::MAX),
+        8 => format!("0o{:o}", i64::MAX),
+        2 => format!("0b{:b}", i64::MAX),
+        _ => i64::MAX.to_string(),
+    };
+    Err(invalid(format!(
+        "does not fit in a signed 64-bit integer (max {})",
+        max
+    )))
+}
+
+/// Returns the radix of an integer literal and its digits without prefix and
+/// separators, or the reason the literal is malformed
+fn number_digits(literal: &str) -> Result<(u32, String), String> {
+    let (radix, article, base, body) = match literal.get(..2) {
+        Some("0x") => (16, "a", "hexadecimal", &literal[2..]),
+        Some("0o") => (8, "an", "octal", &literal[2..]),
+        Some("0b") => (2, "a", "binary", &literal[2..]),
+        _ => (10, "a", "decimal", literal),
+    };
+
+    if let Some(c) = body.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+        return Err(format!("`{}` is not {} {} digit", c, article, base));
+    }
+    let digits: String = body.chars().filter(|&c| c != '_').collect();
+    if digits.is_empty() {
+        return Err(format!("no digits after the {} prefix", base));
+    }
+    Ok((radix, digits))
+}
+
+/// Parses a function call
+///
+/// # Grammar Rule: call
+///
+/// A call consists of the function name followed by a parenthesized,
+/// comma separated list of argument expressions.
+///
+/// Format: `identifier "(" (expression ("," expression)*)? ")"`
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the call
+///
+/// # Returns
+///
+/// An AST node representing the call
+fn parse_call(pair: Pair<Rule>) -> Result<Spanned<ASTNode>, ParseError> {
+    let span = Span::from_pair(&pair);
+    let mut inner = pair.into_inner();
+
+    let name_pair = inner.next().ok_or(ParseError::UnexpectedEnd {
+        expected: Rule::identifier,
+    })?;
+    let name = parse_identifier(&name_pair)?;
+    let args = inner.map(parse_expression).collect::<Result<_, _>>()?;
+
+    Ok(Spanned::new(ASTNode::Call { name, args }, span))
+}
+
+/// Converts an identifier into the name it refers to
+///
+/// # Grammar Rule: identifier
+///
+/// The grammar accepts any XID word so that a reserved keyword used as a
+/// name reaches this point and gets a precise error. With the `nfc` feature
+/// the name is normalized to Unicode NFC, so names that look identical but
+/// are encoded differently refer to the same variable.
+///
+/// # Arguments
+///
+/// * `pair` - The Pest parse tree pair for the identifier
+///
+/// # Returns
+///
+/// The name, normalized if the `nfc` feature is enabled
+fn parse_identifier(pair: &Pair<Rule>) -> Result<String, ParseError> {
+    let name = pair.as_str();
+    if KEYWORDS.contains(&name) {
+        return Err(ParseError::ReservedKeyword {
+            keyword: name.to_string(),
+            span: Span::from_pair(pair),
+        });
+    }
+
+    #[cfg(feature = "nfc")]
+    {
+        use unicode_normalization::UnicodeNormalization;
+        Ok(name.nfc().collect())
+    }
+    #[cfg(not(feature = "nfc"))]
+    Ok(name.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -413,7 +1781,8 @@ mod tests {
             result,
             vec![ASTNode::Assignment {
                 name: "x".to_string(),
-                value: Box::new(ASTNode::Number(5))
+                value: Box::new(ASTNode::Number(5).into()),
+                doc: None,
             }]
         );
     }
@@ -424,9 +1793,27 @@ mod tests {
         assert_eq!(
             result,
             vec![ASTNode::Add(
-                Box::new(ASTNode::Number(1)),
-                Box::new(ASTNode::Number(2))
+                Box::new(ASTNode::Number(1).into()),
+                Box::new(ASTNode::Number(2).into())
             )]
         );
     }
+
+    #[test]
+    fn test_spans_track_source_positions() {
+        let result = parse_program("x = 1;\ny = x / 0;").unwrap();
+        let second = &result[1];
+        assert_eq!((second.span.start, second.span.end), (7, 16));
+        assert_eq!((second.span.line, second.span.column), (2, 1));
+
+        if let ASTNode::Assignment { value, .. } = &second.node {
+            assert_eq!((value.span.line, value.span.column), (2, 5));
+            assert_eq!(
+                &"x = 1;\ny = x / 0;"[value.span.start..value.span.end],
+                "x / 0"
+            );
+        } else {
+            panic!("Expected assignment node");
+        }
+    }
 }
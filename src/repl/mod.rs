@@ -0,0 +1,346 @@
+//! Line-oriented interactive session on top of [`Interpreter`]
+//!
+//! The session keeps a single interpreter alive between inputs, buffers
+//! lines until a statement is complete and understands a handful of
+//! `:`-prefixed meta-commands. Line editing is left to the caller.
+
+use crate::diagnostics::{ColorMode, Diagnostic};
+use crate::interpreter::{BufferSink, Interpreter, Value};
+use crate::parser::{parse_program, ASTNode};
+use std::fmt::Write;
+use std::fs;
+
+/// Name used for REPL input in diagnostics
+const ORIGIN: &str = "<repl>";
+
+/// Help text shown by `:help`
+const HELP: &str = "\
+Enter statements terminated by `;` or a closing `}`. Input may span
+several lines; put `else` on the same line as the preceding `}`.
+
+Meta-commands:
+  :vars          Show all variables
+  :ast <expr>    Show the syntax tree of an expression
+  :reset         Forget all variables and session history
+  :load <file>   Execute a file in the current session
+  :save <file>   Save the statements entered so far to a file
+  :help          Show this message
+  :quit          Leave the REPL";
+
+/// Result of feeding one line to a [`Repl`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// The statement is not complete yet; more input is needed
+    Continue,
+    /// Text to show to the user, possibly empty
+    Output(String),
+    /// A rendered error to show to the user
+    Error(String),
+    /// The user asked to end the session
+    Quit,
+}
+
+/// Interactive session state
+#[derive(Debug)]
+pub struct Repl {
+    interpreter: Interpreter,
+    /// Lines printed by the interpreter, shown in order with statement values
+    output: BufferSink,
+    buffer: String,
+    history: Vec<String>,
+    color: ColorMode,
+}
+
+impl Repl {
+    /// Creates a session with a fresh interpreter
+    ///
+    /// `color` controls how error diagnostics are rendered.
+    pub fn new(color: ColorMode) -> Self {
+        let output = BufferSink::new();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(output.clone());
+        Self {
+            interpreter,
+            output,
+            buffer: String::new(),
+            history: Vec::new(),
+            color,
+        }
+    }
+
+    /// Returns the interpreter backing this session
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    /// Returns `true` while a statement is only partially entered
+    pub fn is_continuing(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /// Discards a partially entered statement
+    pub fn cancel(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Processes one line of user input
+    pub fn handle_line(&mut self, line: &str) -> Reply {
+        let trimmed = line.trim();
+        if self.buffer.is_empty() {
+            if trimmed.is_empty() {
+                return Reply::Output(String::new());
+            }
+            if let Some(command) = trimmed.strip_prefix(':') {
+                return self.meta_command(command);
+            }
+        }
+
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+        if !is_complete(&self.buffer) {
+            return Reply::Continue;
+        }
+
+        let source = std::mem::take(&mut self.buffer);
+        self.execute(&source, ORIGIN, true)
+    }
+
+    /// Parses and runs `source`, printing the value of expression statements
+    fn execute(&mut self, source: &str, origin: &str, show_values: bool) -> Reply {
+        let ast = match parse_program(source) {
+            Ok(ast) => ast,
+            Err(e) => return Reply::Error(Diagnostic::from(&e).render(source, origin, self.color)),
+        };
+
+        let mut output = String::new();
+        for node in &ast {
+            let result = self.interpreter.eval_statement(node);
+            for line in self.output.take() {
+                let _ = writeln!(output, "{}", line);
+            }
+            match result {
+                Ok(value) => {
+                    if let Some(value) = value.filter(|v| show_values && *v != Value::Unit) {
+                        if !matches!(
+                            node.node,
+                            ASTNode::Assignment { .. } | ASTNode::IndexAssignment { .. }
+                        ) {
+                            let _ = writeln!(output, "{}", value);
+                        }
+                    }
+                }
+                Err(e) => {
+                    //Statements before the failing one already ran, keep them for :save
+                    self.record(&source[..node.span.start]);
+                    let diagnostic = self.interpreter.diagnose(&e);
+                    //Keep what the script printed before failing
+                    output.push_str(&diagnostic.render(source, origin, self.color));
+                    return Reply::Error(output);
+                }
+            }
+        }
+
+        self.record(source);
+        Reply::Output(output.trim_end().to_string())
+    }
+
+    /// Remembers successfully executed source for `:save`
+    fn record(&mut self, source: &str) {
+        let source = source.trim();
+        if !source.is_empty() {
+            self.history.push(source.to_string());
+        }
+    }
+
+    fn meta_command(&mut self, command: &str) -> Reply {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+
+        match name {
+            "vars" => {
+                let mut names: Vec<_> = self.interpreter.variables.iter().collect();
+                names.sort_by(|a, b| a.0.cmp(b.0));
+                let listing: Vec<String> = names
+                    .into_iter()
+                    .map(|(name, value)| format!("{} = {}", name, value))
+                    .collect();
+                Reply::Output(listing.join("\n"))
+            }
+            "ast" => {
+                if argument.is_empty() {
+                    return Reply::Error("usage: :ast <expr>".to_string());
+                }
+                let source = if argument.ends_with(';') {
+                    argument.to_string()
+                } else {
+                    format!("{};", argument)
+                };
+                match parse_program(&source) {
+                    Ok(ast) => Reply::Output(
+                        ast.iter()
+                            .map(|node| format!("{:#?}", node))
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    ),
+                    Err(e) => {
+                        Reply::Error(Diagnostic::from(&e).render(&source, ORIGIN, self.color))
+                    }
+                }
+            }
+            "reset" => {
+                self.interpreter = Interpreter::new();
+                self.interpreter.set_output(self.output.clone());
+                self.history.clear();
+                Reply::Output("Session reset.".to_string())
+            }
+            "load" => {
+                if argument.is_empty() {
+                    return Reply::Error("usage: :load <file>".to_string());
+                }
+                match fs::read_to_string(argument) {
+                    Ok(source) => match self.execute(&source, argument, false) {
+                        //What the file printed comes before the confirmation
+                        Reply::Output(mut output) => {
+                            if !output.is_empty() {
+                                output.push('\n');
+                            }
+                            let _ = write!(output, "Loaded {}.", argument);
+                            Reply::Output(output)
+                        }
+                        reply => reply,
+                    },
+                    Err(e) => Reply::Error(format!("Failed to read file {}: {}", argument, e)),
+                }
+            }
+            "save" => {
+                if argument.is_empty() {
+                    return Reply::Error("usage: :save <file>".to_string());
+                }
+                let mut contents = self.history.join("\n");
+                contents.push('\n');
+                match fs::write(argument, contents) {
+                    Ok(()) => Reply::Output(format!("Saved session to {}.", argument)),
+                    Err(e) => Reply::Error(format!("Failed to write file {}: {}", argument, e)),
+                }
+            }
+            "help" => Reply::Output(HELP.to_string()),
+            "quit" | "q" | "exit" => Reply::Quit,
+            _ => Reply::Error(format!(
+                "unknown command `:{}`, type :help for a list of commands",
+                name
+            )),
+        }
+    }
+}
+
+/// Where [`is_complete`] is while scanning input
+enum Context {
+    /// Code, with the braces opened in it, `true` for those opening a block
+    /// rather than a map
+    Code(Vec<bool>),
+    /// Inside a string literal
+    String,
+}
+
+/// Returns `true` once `source` ends a statement: every block, map, block
+/// comment and string is closed and the last code outside comments is `;`
+/// or the `}` of a block
+///
+/// Braces inside a string open an interpolation, which is code again until
+/// its closing brace. Other braces open a block after `)`, `else` or the
+/// end of a `for` loop's iterable, and a map anywhere else.
+fn is_complete(source: &str) -> bool {
+    let mut contexts = vec![Context::Code(Vec::new())];
+    let mut comment_depth = 0usize;
+    let mut last = None;
+    //The word being scanned, or the last one if only whitespace followed it
+    let mut word = String::new();
+    //Whether whitespace or a comment came after `last`, ending its word
+    let mut spaced = false;
+    //Whether `last` closed a map, ending an expression
+    let mut closed_map = false;
+    let mut ends_statement = false;
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        if comment_depth > 0 {
+            match (c, chars.peek()) {
+                ('*', Some('/')) => {
+                    chars.next();
+                    comment_depth -= 1;
+                }
+                ('/', Some('*')) => {
+                    chars.next();
+                    comment_depth += 1;
+                }
+                _ => {}
+            }
+            continue;
+        }
+        let nested = contexts.len() > 1;
+        let Some(Context::Code(braces)) = contexts.last_mut() else {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '"' => {
+                    contexts.pop();
+                }
+                '{' => contexts.push(Context::Code(Vec::new())),
+                _ => {}
+            }
+            continue;
+        };
+        match (c, chars.peek()) {
+            ('/', Some('/')) => while chars.next_if(|&c| c != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                comment_depth = 1;
+                spaced = true;
+            }
+            //The brace closing an interpolation returns to its string
+            ('}', _) if nested && braces.is_empty() => {
+                contexts.pop();
+            }
+            _ if c.is_whitespace() => spaced = true,
+            _ => {
+                let mut closes_block = false;
+                match c {
+                    '{' => braces.push(closed_map || opens_block(last, &word)),
+                    //A stray `}` is left for the parser to report
+                    '}' => closes_block = braces.pop().unwrap_or(true),
+                    '"' => contexts.push(Context::String),
+                    _ => {}
+                }
+                if c == '_' || c.is_alphanumeric() {
+                    if spaced || !last.is_some_and(|l: char| l == '_' || l.is_alphanumeric()) {
+                        word.clear();
+                    }
+                    word.push(c);
+                } else {
+                    word.clear();
+                }
+                ends_statement = c == ';' || closes_block;
+                closed_map = c == '}' && !closes_block;
+                last = Some(c);
+                spaced = false;
+            }
+        }
+    }
+    let closed = matches!(contexts.as_slice(), [Context::Code(braces)] if braces.is_empty());
+    closed && comment_depth == 0 && ends_statement
+}
+
+/// Returns `true` if a `{` following the character `last` and the word
+/// `word` opens a block rather than a map
+///
+/// Maps only start where an expression may, so a brace after the end of a
+/// name, literal or index is the body of a `for` loop.
+fn opens_block(last: Option<char>, word: &str) -> bool {
+    match last {
+        Some(')' | ']' | '"') => true,
+        Some(c) if c == '_' || c.is_alphanumeric() => !matches!(word, "return" | "print" | "in"),
+        _ => false,
+    }
+}
@@ -0,0 +1,1074 @@
+//! Bytecode compiler and stack-based virtual machine
+//!
+//! The compiler resolves every variable name to a numeric slot ahead of
+//! time, so the VM never hashes strings while running. Programs executed by
+//! the VM produce the same values and errors as [`Interpreter`].
+//!
+//! [`Interpreter`]: crate::Interpreter
+
+use crate::interpreter::{
+    assign_index, binary_op, bit_not, check_number, format_line, index, interpolate, iterate,
+    local_names, make_map, negate, range, slice, Arithmetic, BinaryOp, EvalError,
+    FloatDivisionMode, IntegerDivisionMode, Iteration, Natives, OutputSink, OverflowMode,
+    StdoutSink, Value, DEFAULT_MAX_CALL_DEPTH,
+};
+use crate::parser::{ASTNode, Span, Spanned};
+use std::collections::HashMap;
+
+/// A single VM instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// Pushes the constant with the given index
+    Constant(usize),
+    /// Pushes the value of a variable slot
+    Load(usize),
+    /// Stores the top of the stack into a slot, leaving it on the stack
+    Store(usize),
+    /// Pushes the value of a local slot of the current call
+    LoadLocal(usize),
+    /// Stores the top of the stack into a local slot of the current call,
+    /// leaving it on the stack
+    StoreLocal(usize),
+    /// Pops a value and the given number of indexes, replaces the element
+    /// they select in the array of a variable slot, and pushes the value back
+    StoreIndex(usize, usize),
+    /// Like [`Instruction::StoreIndex`], for a local slot of the current call
+    StoreIndexLocal(usize, usize),
+    /// Fails unless the variable is defined, before the index of an
+    /// [`Instruction::LoadIndex`] from it is computed
+    CheckDefined(Slot),
+    /// Pops an index and pushes the element it selects in the value of the
+    /// variable, without copying the rest of the value
+    LoadIndex(Slot),
+    /// Pops the top of the stack
+    Pop,
+    /// Pops two values and pushes their sum
+    Add,
+    /// Pops two values and pushes their difference
+    Sub,
+    /// Pops two values and pushes their product
+    Mul,
+    /// Pops two values and pushes their quotient
+    Div,
+    /// Pops two values and pushes the remainder of their division
+    Mod,
+    /// Pops two values and pushes the first raised to the second
+    Pow,
+    /// Pops two values and pushes their bitwise and
+    BitAnd,
+    /// Pops two values and pushes their bitwise or
+    BitOr,
+    /// Pops two values and pushes their bitwise exclusive or
+    BitXor,
+    /// Pops two values and pushes the first shifted left by the second
+    Shl,
+    /// Pops two values and pushes the first shifted right by the second
+    Shr,
+    /// Pops two values and pushes whether they are equal
+    Eq,
+    /// Pops two values and pushes whether they differ
+    Ne,
+    /// Pops two values and pushes whether the first is less than the second
+    Lt,
+    /// Pops two values and pushes whether the first is at most the second
+    Le,
+    /// Pops two values and pushes whether the first is greater than the second
+    Gt,
+    /// Pops two values and pushes whether the first is at least the second
+    Ge,
+    /// Pops a boolean and pushes its negation
+    Not,
+    /// Pops a number and pushes its negation
+    Neg,
+    /// Pops an int and pushes its bitwise complement
+    BitNot,
+    /// Fails unless the top of the stack is a number
+    CheckNumber,
+    /// Fails unless the top of the stack is a boolean
+    CheckBool,
+    /// Continues execution at the given instruction
+    Jump(usize),
+    /// Pops a boolean and jumps to the given instruction if it is false
+    JumpIfFalse(usize),
+    /// Makes the function with the given index callable under its name
+    Define(usize),
+    /// Pops the given number of arguments and calls the function in the
+    /// given function slot with them
+    Call(usize, usize),
+    /// Ends the current call, leaving its result on top of the stack for
+    /// the caller
+    Return,
+    /// Pops the given number of values and pushes their formatted text
+    /// joined into one string
+    Interpolate(usize),
+    /// Pops the given number of values and pushes an array of them
+    Array(usize),
+    /// Pops the given number of keys, each followed by its value, and
+    /// pushes a map of them
+    Map(usize),
+    /// Pops an index and a value and pushes the element at that index
+    Index,
+    /// Pops the bounds that are present, end last, and a value, and pushes
+    /// the slice between them
+    Slice {
+        /// Whether a start bound was given
+        start: bool,
+        /// Whether an end bound was given
+        end: bool,
+    },
+    /// Pops the given number of values and writes them as one output line
+    Print(usize),
+    /// Pops an array, string or map and starts a `for` loop over its
+    /// elements, assigning them to the given variable
+    Iterate(Slot),
+    /// Pops an end and a start and starts a `for` loop over the ints
+    /// between them, including the end if the flag is set
+    IterateRange(Slot, bool),
+    /// Assigns the next value of the innermost `for` loop to its variable,
+    /// or jumps to the given instruction once there are none left
+    ForNext(usize),
+    /// Ends the innermost `for` loop, giving its variable back the value it
+    /// had before the loop
+    ForEnd,
+}
+
+/// A variable slot, either global or local to the current call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    /// A slot of [`Chunk::names`]
+    Global(usize),
+    /// A local slot of the current call
+    Local(usize),
+}
+
+/// Compiled form of a program
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chunk {
+    /// Instructions in execution order
+    pub code: Vec<Instruction>,
+    /// Source span of each instruction, used for error reporting
+    pub spans: Vec<Span>,
+    /// Constant pool referenced by [`Instruction::Constant`]
+    pub constants: Vec<Value>,
+    /// Variable name of each slot
+    pub names: Vec<String>,
+    /// Functions referenced by [`Instruction::Define`]
+    pub functions: Vec<CompiledFunction>,
+    /// Function name of each function slot
+    pub function_names: Vec<String>,
+}
+
+/// A function compiled into a [`Chunk`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    /// Function slot the function is defined in
+    pub slot: usize,
+    /// Number of parameters
+    pub arity: usize,
+    /// Position of the first instruction of the body
+    pub entry: usize,
+    /// Variable name of each local slot; parameters come first
+    pub locals: Vec<String>,
+}
+
+/// Compiles a parsed program into bytecode
+///
+/// # Arguments
+///
+/// * `nodes` - The statements returned by [`parse_program`](crate::parse_program)
+///
+/// # Returns
+///
+/// A chunk ready to be executed by [`Vm::run`]
+pub fn compile(nodes: &[Spanned<ASTNode>]) -> Chunk {
+    let mut compiler = Compiler::default();
+    compiler.compile_block(nodes);
+    compiler.chunk
+}
+
+#[derive(Default)]
+struct Compiler {
+    chunk: Chunk,
+    slots: HashMap<String, usize>,
+    function_slots: HashMap<String, usize>,
+    /// Loops enclosing the code being compiled, innermost last
+    loops: Vec<Loop>,
+    /// Local names of the function being compiled, empty at the top level
+    locals: Vec<String>,
+}
+
+/// Jump bookkeeping for a loop being compiled
+struct Loop {
+    /// Position of the condition check, where `continue` jumps to
+    start: usize,
+    /// `break` jumps waiting to be pointed past the end of the loop
+    breaks: Vec<usize>,
+}
+
+impl Compiler {
+    fn emit(&mut self, instruction: Instruction, span: Span) {
+        self.chunk.code.push(instruction);
+        self.chunk.spans.push(span);
+    }
+
+    fn slot(&mut self, name: &str) -> usize {
+        if let Some(&slot) = self.slots.get(name) {
+            return slot;
+        }
+        let slot = self.chunk.names.len();
+        self.chunk.names.push(name.to_string());
+        self.slots.insert(name.to_string(), slot);
+        slot
+    }
+
+    fn function_slot(&mut self, name: &str) -> usize {
+        if let Some(&slot) = self.function_slots.get(name) {
+            return slot;
+        }
+        let slot = self.chunk.function_names.len();
+        self.chunk.function_names.push(name.to_string());
+        self.function_slots.insert(name.to_string(), slot);
+        slot
+    }
+
+    /// Returns the local slot of `name` in the function being compiled
+    fn local(&self, name: &str) -> Option<usize> {
+        self.locals.iter().position(|local| local == name)
+    }
+
+    fn emit_constant(&mut self, value: Value, span: Span) {
+        let index = self.chunk.constants.len();
+        self.chunk.constants.push(value);
+        self.emit(Instruction::Constant(index), span);
+    }
+
+    /// Emits a jump with a placeholder target and returns its position
+    fn emit_jump(&mut self, instruction: fn(usize) -> Instruction, span: Span) -> usize {
+        self.emit(instruction(usize::MAX), span);
+        self.chunk.code.len() - 1
+    }
+
+    /// Points the jump at `position` to the next instruction to be emitted
+    fn patch_jump(&mut self, position: usize) {
+        let target = self.chunk.code.len();
+        self.chunk.code[position] = match self.chunk.code[position] {
+            Instruction::Jump(_) => Instruction::Jump(target),
+            Instruction::JumpIfFalse(_) => Instruction::JumpIfFalse(target),
+            Instruction::ForNext(_) => Instruction::ForNext(target),
+            other => unreachable!("patching non-jump instruction {:?}", other),
+        };
+    }
+
+    fn compile_block(&mut self, nodes: &[Spanned<ASTNode>]) {
+        for node in nodes {
+            self.compile_statement(node);
+        }
+    }
+
+    /// Compiles a statement, leaving the stack as it was before
+    fn compile_statement(&mut self, node: &Spanned<ASTNode>) {
+        match &node.node {
+            ASTNode::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.compile_node(condition);
+                let skip_then = self.emit_jump(Instruction::JumpIfFalse, condition.span);
+                self.compile_block(then_branch);
+                match else_branch {
+                    Some(else_branch) => {
+                        let skip_else = self.emit_jump(Instruction::Jump, node.span);
+                        self.patch_jump(skip_then);
+                        self.compile_block(else_branch);
+                        self.patch_jump(skip_else);
+                    }
+                    None => self.patch_jump(skip_then),
+                }
+            }
+            ASTNode::While { condition, body } => {
+                let start = self.chunk.code.len();
+                self.compile_node(condition);
+                let exit = self.emit_jump(Instruction::JumpIfFalse, condition.span);
+                self.loops.push(Loop {
+                    start,
+                    breaks: Vec::new(),
+                });
+                self.compile_block(body);
+                self.emit(Instruction::Jump(start), node.span);
+                self.patch_jump(exit);
+                let finished = self.loops.pop().expect("loop pushed above");
+                for jump in finished.breaks {
+                    self.patch_jump(jump);
+                }
+            }
+            ASTNode::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                let slot = match self.local(variable) {
+                    Some(slot) => Slot::Local(slot),
+                    None => Slot::Global(self.slot(variable)),
+                };
+                match &iterable.node {
+                    ASTNode::Range {
+                        start,
+                        end,
+                        inclusive,
+                    } => {
+                        self.compile_node(start);
+                        self.compile_node(end);
+                        self.emit(Instruction::IterateRange(slot, *inclusive), iterable.span);
+                    }
+                    _ => {
+                        self.compile_node(iterable);
+                        self.emit(Instruction::Iterate(slot), iterable.span);
+                    }
+                }
+                let start = self.chunk.code.len();
+                let exit = self.emit_jump(Instruction::ForNext, node.span);
+                self.loops.push(Loop {
+                    start,
+                    breaks: Vec::new(),
+                });
+                self.compile_block(body);
+                self.emit(Instruction::Jump(start), node.span);
+                self.patch_jump(exit);
+                let finished = self.loops.pop().expect("loop pushed above");
+                for jump in finished.breaks {
+                    self.patch_jump(jump);
+                }
+                self.emit(Instruction::ForEnd, node.span);
+            }
+            ASTNode::FunctionDef { name, params, body } => {
+                let index = self.chunk.functions.len();
+                let slot = self.function_slot(name);
+                let locals = local_names(params, body);
+                self.chunk.functions.push(CompiledFunction {
+                    slot,
+                    arity: params.len(),
+                    entry: usize::MAX,
+                    locals: locals.clone(),
+                });
+                self.emit(Instruction::Define(index), node.span);
+
+                //The body is only entered through calls
+                let skip_body = self.emit_jump(Instruction::Jump, node.span);
+                self.chunk.functions[index].entry = self.chunk.code.len();
+                let outer_locals = std::mem::replace(&mut self.locals, locals);
+                self.compile_block(body);
+                self.emit_constant(Value::Unit, node.span);
+                self.emit(Instruction::Return, node.span);
+                self.locals = outer_locals;
+                self.patch_jump(skip_body);
+            }
+            ASTNode::Return(value) => {
+                match value {
+                    Some(value) => self.compile_node(value),
+                    None => self.emit_constant(Value::Unit, node.span),
+                }
+                self.emit(Instruction::Return, node.span);
+            }
+            ASTNode::Print(values) => {
+                for value in values {
+                    self.compile_node(value);
+                }
+                self.emit(Instruction::Print(values.len()), node.span);
+            }
+            ASTNode::Break => {
+                let jump = self.emit_jump(Instruction::Jump, node.span);
+                if let Some(innermost) = self.loops.last_mut() {
+                    innermost.breaks.push(jump);
+                }
+            }
+            ASTNode::Continue => {
+                if let Some(innermost) = self.loops.last() {
+                    self.emit(Instruction::Jump(innermost.start), node.span);
+                }
+            }
+            _ => {
+                self.compile_node(node);
+                self.emit(Instruction::Pop, node.span);
+            }
+        }
+    }
+
+    /// Compiles an expression, leaving its value on the stack
+    fn compile_node(&mut self, node: &Spanned<ASTNode>) {
+        match &node.node {
+            ASTNode::Number(n) => self.emit_constant(Value::Int(*n), node.span),
+            ASTNode::Float(x) => self.emit_constant(Value::Float(*x), node.span),
+            #[cfg(feature = "bigint")]
+            ASTNode::BigNumber(n) => self.emit_constant(Value::from(n.clone()), node.span),
+            ASTNode::Bool(b) => self.emit_constant(Value::Bool(*b), node.span),
+            ASTNode::String(s) => self.emit_constant(Value::String(s.clone()), node.span),
+            ASTNode::Interpolation(parts) => {
+                for part in parts {
+                    self.compile_node(part);
+                }
+                self.emit(Instruction::Interpolate(parts.len()), node.span);
+            }
+            ASTNode::Identifier(name) => {
+                let instruction = match self.local(name) {
+                    Some(slot) => Instruction::LoadLocal(slot),
+                    None => Instruction::Load(self.slot(name)),
+                };
+                self.emit(instruction, node.span);
+            }
+            ASTNode::Assignment { name, value, .. } => {
+                self.compile_node(value);
+                let instruction = match self.local(name) {
+                    Some(slot) => Instruction::StoreLocal(slot),
+                    None => Instruction::Store(self.slot(name)),
+                };
+                self.emit(instruction, node.span);
+            }
+            ASTNode::IndexAssignment {
+                name,
+                indexes,
+                value,
+            } => {
+                for index in indexes {
+                    self.compile_node(index);
+                }
+                self.compile_node(value);
+                let instruction = match self.local(name) {
+                    Some(slot) => Instruction::StoreIndexLocal(slot, indexes.len()),
+                    None => Instruction::StoreIndex(self.slot(name), indexes.len()),
+                };
+                self.emit(instruction, node.span);
+            }
+            ASTNode::Array(elements) => {
+                for element in elements {
+                    self.compile_node(element);
+                }
+                self.emit(Instruction::Array(elements.len()), node.span);
+            }
+            ASTNode::Map(entries) => {
+                for (key, value) in entries {
+                    self.compile_node(key);
+                    self.compile_node(value);
+                }
+                self.emit(Instruction::Map(entries.len()), node.span);
+            }
+            ASTNode::Call { name, args } => {
+                for arg in args {
+                    self.compile_node(arg);
+                }
+                let slot = self.function_slot(name);
+                self.emit(Instruction::Call(slot, args.len()), node.span);
+            }
+            ASTNode::Index { target, index } => match &target.node {
+                ASTNode::Identifier(name) => {
+                    let slot = match self.local(name) {
+                        Some(slot) => Slot::Local(slot),
+                        None => Slot::Global(self.slot(name)),
+                    };
+                    self.emit(Instruction::CheckDefined(slot), target.span);
+                    self.compile_node(index);
+                    self.emit(Instruction::LoadIndex(slot), node.span);
+                }
+                _ => {
+                    self.compile_node(target);
+                    self.compile_node(index);
+                    self.emit(Instruction::Index, node.span);
+                }
+            },
+            ASTNode::Slice { target, start, end } => {
+                self.compile_node(target);
+                for bound in [start, end].into_iter().flatten() {
+                    self.compile_node(bound);
+                }
+                let instruction = Instruction::Slice {
+                    start: start.is_some(),
+                    end: end.is_some(),
+                };
+                self.emit(instruction, node.span);
+            }
+            ASTNode::Add(l, r) => self.compile_binary(l, r, Instruction::Add, node.span),
+            ASTNode::Sub(l, r) => self.compile_binary(l, r, Instruction::Sub, node.span),
+            ASTNode::Mul(l, r) => self.compile_binary(l, r, Instruction::Mul, node.span),
+            ASTNode::Div(l, r) => self.compile_binary(l, r, Instruction::Div, node.span),
+            ASTNode::Mod(l, r) => self.compile_binary(l, r, Instruction::Mod, node.span),
+            ASTNode::Pow(l, r) => self.compile_binary(l, r, Instruction::Pow, node.span),
+            ASTNode::BitAnd(l, r) => self.compile_binary(l, r, Instruction::BitAnd, node.span),
+            ASTNode::BitOr(l, r) => self.compile_binary(l, r, Instruction::BitOr, node.span),
+            ASTNode::BitXor(l, r) => self.compile_binary(l, r, Instruction::BitXor, node.span),
+            ASTNode::Shl(l, r) => self.compile_binary(l, r, Instruction::Shl, node.span),
+            ASTNode::Shr(l, r) => self.compile_binary(l, r, Instruction::Shr, node.span),
+            ASTNode::Eq(l, r) => self.compile_binary(l, r, Instruction::Eq, node.span),
+            ASTNode::Ne(l, r) => self.compile_binary(l, r, Instruction::Ne, node.span),
+            ASTNode::Lt(l, r) => self.compile_binary(l, r, Instruction::Lt, node.span),
+            ASTNode::Le(l, r) => self.compile_binary(l, r, Instruction::Le, node.span),
+            ASTNode::Gt(l, r) => self.compile_binary(l, r, Instruction::Gt, node.span),
+            ASTNode::Ge(l, r) => self.compile_binary(l, r, Instruction::Ge, node.span),
+            ASTNode::And(l, r) => {
+                // l ? (r checked as bool) : false
+                self.compile_node(l);
+                let short_circuit = self.emit_jump(Instruction::JumpIfFalse, l.span);
+                self.compile_node(r);
+                self.emit(Instruction::CheckBool, r.span);
+                let end = self.emit_jump(Instruction::Jump, node.span);
+                self.patch_jump(short_circuit);
+                self.emit_constant(Value::Bool(false), node.span);
+                self.patch_jump(end);
+            }
+            ASTNode::Or(l, r) => {
+                // l ? true : (r checked as bool)
+                self.compile_node(l);
+                let evaluate_right = self.emit_jump(Instruction::JumpIfFalse, l.span);
+                self.emit_constant(Value::Bool(true), node.span);
+                let end = self.emit_jump(Instruction::Jump, node.span);
+                self.patch_jump(evaluate_right);
+                self.compile_node(r);
+                self.emit(Instruction::CheckBool, r.span);
+                self.patch_jump(end);
+            }
+            ASTNode::Not(operand) => {
+                self.compile_node(operand);
+                self.emit(Instruction::Not, operand.span);
+            }
+            ASTNode::Neg(operand) => {
+                self.compile_node(operand);
+                self.emit(Instruction::CheckNumber, operand.span);
+                self.emit(Instruction::Neg, node.span);
+            }
+            ASTNode::BitNot(operand) => {
+                self.compile_node(operand);
+                self.emit(Instruction::BitNot, operand.span);
+            }
+            ASTNode::Plus(operand) => {
+                self.compile_node(operand);
+                self.emit(Instruction::CheckNumber, operand.span);
+            }
+            ASTNode::Range { .. } => unreachable!("ranges only appear in for loops"),
+            ASTNode::If { .. }
+            | ASTNode::While { .. }
+            | ASTNode::For { .. }
+            | ASTNode::FunctionDef { .. }
+            | ASTNode::Return(_)
+            | ASTNode::Print(_)
+            | ASTNode::Break
+            | ASTNode::Continue => {
+                unreachable!("the parser never places statements inside expressions")
+            }
+        }
+    }
+
+    fn compile_binary(
+        &mut self,
+        left: &Spanned<ASTNode>,
+        right: &Spanned<ASTNode>,
+        instruction: Instruction,
+        span: Span,
+    ) {
+        self.compile_node(left);
+        self.compile_node(right);
+        self.emit(instruction, span);
+    }
+}
+
+/// Stack-based virtual machine that executes compiled chunks
+#[derive(Debug)]
+pub struct Vm {
+    stack: Vec<Value>,
+    slots: Vec<Option<Value>>,
+    names: Vec<String>,
+    /// Index into [`Chunk::functions`] defined in each function slot
+    function_slots: Vec<Option<usize>>,
+    function_names: Vec<String>,
+    /// Functions implemented by the host application
+    natives: Natives,
+    /// Calls in progress, innermost last
+    frames: Vec<CallFrame>,
+    /// `for` loops in progress, innermost last
+    loops: Vec<ForLoop>,
+    max_call_depth: usize,
+    arithmetic: Arithmetic,
+    /// Destination of `print` statements
+    output: Box<dyn OutputSink>,
+}
+
+/// Local state of a function call in progress
+#[derive(Debug)]
+struct CallFrame {
+    /// Index into [`Chunk::functions`]
+    function: usize,
+    /// Instruction to continue with after the call returns
+    return_pc: usize,
+    locals: Vec<Option<Value>>,
+    /// Number of `for` loops in progress when the call started
+    loops: usize,
+}
+
+/// A `for` loop in progress
+#[derive(Debug)]
+struct ForLoop {
+    iteration: Iteration,
+    variable: Slot,
+    /// Value of the variable before the loop started
+    previous: Option<Value>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    /// Creates a new VM with empty variable state
+    ///
+    /// The same standard library as [`Interpreter::new`] is registered.
+    ///
+    /// [`Interpreter::new`]: crate::Interpreter::new
+    pub fn new() -> Self {
+        Self {
+            natives: Natives::stdlib(),
+            ..Self::without_stdlib()
+        }
+    }
+
+    /// Creates a new VM without any native functions
+    pub fn without_stdlib() -> Self {
+        Self {
+            stack: Vec::new(),
+            slots: Vec::new(),
+            names: Vec::new(),
+            function_slots: Vec::new(),
+            function_names: Vec::new(),
+            natives: Natives::default(),
+            frames: Vec::new(),
+            loops: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            arithmetic: Arithmetic::default(),
+            output: Box::new(StdoutSink),
+        }
+    }
+
+    /// Sets how integer arithmetic behaves when a result does not fit in
+    /// an `i64`
+    ///
+    /// Behaves like [`Interpreter::set_overflow_mode`].
+    ///
+    /// [`Interpreter::set_overflow_mode`]: crate::Interpreter::set_overflow_mode
+    pub fn set_overflow_mode(&mut self, mode: OverflowMode) {
+        self.arithmetic.overflow = mode;
+    }
+
+    /// Sets what dividing two integers produces
+    ///
+    /// Behaves like [`Interpreter::set_integer_division_mode`].
+    ///
+    /// [`Interpreter::set_integer_division_mode`]: crate::Interpreter::set_integer_division_mode
+    pub fn set_integer_division_mode(&mut self, mode: IntegerDivisionMode) {
+        self.arithmetic.integer_division = mode;
+    }
+
+    /// Sets what dividing a float by zero produces
+    ///
+    /// Behaves like [`Interpreter::set_float_division_mode`].
+    ///
+    /// [`Interpreter::set_float_division_mode`]: crate::Interpreter::set_float_division_mode
+    pub fn set_float_division_mode(&mut self, mode: FloatDivisionMode) {
+        self.arithmetic.float_division = mode;
+    }
+
+    /// Makes a Rust function callable from scripts as `name`
+    ///
+    /// Behaves like [`Interpreter::register_fn`].
+    ///
+    /// [`Interpreter::register_fn`]: crate::Interpreter::register_fn
+    pub fn register_fn<F>(&mut self, name: impl Into<String>, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.natives.register(name, arity, function);
+    }
+
+    /// Removes a native function, including standard library functions
+    ///
+    /// Returns `true` if a function with that name was registered.
+    pub fn unregister_fn(&mut self, name: &str) -> bool {
+        self.natives.remove(name)
+    }
+
+    /// Returns the names of the registered native functions
+    pub fn native_functions(&self) -> impl Iterator<Item = &str> {
+        self.natives.names().map(String::as_str)
+    }
+
+    /// Sets how many calls may be nested before a call fails with
+    /// [`EvalError::StackOverflow`]
+    ///
+    /// Defaults to the same limit as the interpreter.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    /// Sends the lines written by `print` statements to `sink`
+    ///
+    /// Behaves like [`Interpreter::set_output`].
+    ///
+    /// [`Interpreter::set_output`]: crate::Interpreter::set_output
+    pub fn set_output(&mut self, sink: impl OutputSink + 'static) {
+        self.output = Box::new(sink);
+    }
+
+    /// Executes a chunk from the beginning
+    ///
+    /// Variable state from a previous run is discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same `EvalError` the interpreter would for the program
+    pub fn run(&mut self, chunk: &Chunk) -> Result<(), EvalError> {
+        self.stack.clear();
+        self.frames.clear();
+        self.loops.clear();
+        self.slots = vec![None; chunk.names.len()];
+        self.names = chunk.names.clone();
+        self.function_slots = vec![None; chunk.function_names.len()];
+        self.function_names = chunk.function_names.clone();
+
+        let result = self.execute(chunk);
+        if result.is_err() {
+            self.unwind();
+        }
+        result
+    }
+
+    /// Executes the instructions of `chunk`, stopping at the first error
+    fn execute(&mut self, chunk: &Chunk) -> Result<(), EvalError> {
+        let mut pc = 0;
+        while let Some(&instruction) = chunk.code.get(pc) {
+            let span = chunk.spans[pc];
+            pc += 1;
+            match instruction {
+                Instruction::Constant(index) => self.stack.push(chunk.constants[index].clone()),
+                Instruction::Load(slot) => match &self.slots[slot] {
+                    Some(value) => self.stack.push(value.clone()),
+                    None => {
+                        return Err(EvalError::UndefinedVariable {
+                            name: chunk.names[slot].clone(),
+                            span,
+                        })
+                    }
+                },
+                Instruction::Store(slot) => self.slots[slot] = Some(self.peek().clone()),
+                Instruction::LoadLocal(slot) => {
+                    let frame = self.frame();
+                    match &frame.locals[slot] {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => {
+                            return Err(EvalError::UndefinedVariable {
+                                name: chunk.functions[frame.function].locals[slot].clone(),
+                                span,
+                            })
+                        }
+                    }
+                }
+                Instruction::StoreLocal(slot) => {
+                    let value = self.peek().clone();
+                    self.frames
+                        .last_mut()
+                        .expect("locals are only used inside functions")
+                        .locals[slot] = Some(value);
+                }
+                Instruction::StoreIndex(slot, depth) => {
+                    let value = self.pop();
+                    let indexes = self.stack.split_off(self.stack.len() - depth);
+                    let Some(target) = &mut self.slots[slot] else {
+                        return Err(EvalError::UndefinedVariable {
+                            name: chunk.names[slot].clone(),
+                            span,
+                        });
+                    };
+                    assign_index(target, indexes, value.clone(), span)?;
+                    self.stack.push(value);
+                }
+                Instruction::StoreIndexLocal(slot, depth) => {
+                    let value = self.pop();
+                    let indexes = self.stack.split_off(self.stack.len() - depth);
+                    let frame = self
+                        .frames
+                        .last_mut()
+                        .expect("locals are only used inside functions");
+                    let Some(target) = &mut frame.locals[slot] else {
+                        return Err(EvalError::UndefinedVariable {
+                            name: chunk.functions[frame.function].locals[slot].clone(),
+                            span,
+                        });
+                    };
+                    assign_index(target, indexes, value.clone(), span)?;
+                    self.stack.push(value);
+                }
+                Instruction::Pop => {
+                    self.pop();
+                }
+                Instruction::Array(count) => {
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::Array(elements));
+                }
+                Instruction::Map(count) => {
+                    let entries = self.stack.split_off(self.stack.len() - count * 2);
+                    self.stack.push(make_map(entries));
+                }
+                Instruction::Interpolate(count) => {
+                    let parts = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(interpolate(&parts));
+                }
+                Instruction::CheckDefined(slot) => {
+                    if self.variable(slot).is_none() {
+                        return Err(self.undefined(chunk, slot, span));
+                    }
+                }
+                Instruction::LoadIndex(slot) => {
+                    let i = self.pop();
+                    let Some(target) = self.variable(slot) else {
+                        return Err(self.undefined(chunk, slot, span));
+                    };
+                    let element = index(target, i, span)?;
+                    self.stack.push(element);
+                }
+                Instruction::Index => {
+                    let i = self.pop();
+                    let target = self.pop();
+                    self.stack.push(index(&target, i, span)?);
+                }
+                Instruction::Slice { start, end } => {
+                    let end = end.then(|| self.pop());
+                    let start = start.then(|| self.pop());
+                    let target = self.pop();
+                    self.stack.push(slice(target, start, end, span)?);
+                }
+                Instruction::Print(count) => {
+                    let values = self.stack.split_off(self.stack.len() - count);
+                    self.output.write_line(&format_line(&values));
+                }
+                Instruction::Iterate(variable) => {
+                    let collection = self.pop();
+                    let iteration = iterate(collection, span)?;
+                    self.start_loop(iteration, variable);
+                }
+                Instruction::IterateRange(variable, inclusive) => {
+                    let end = self.pop();
+                    let start = self.pop();
+                    let iteration = range(start, end, inclusive, span)?;
+                    self.start_loop(iteration, variable);
+                }
+                Instruction::ForNext(exit) => {
+                    let innermost = self
+                        .loops
+                        .last_mut()
+                        .expect("compiled code only advances started loops");
+                    let variable = innermost.variable;
+                    match innermost.iteration.next() {
+                        Some(value) => *self.variable(variable) = Some(value),
+                        None => pc = exit,
+                    }
+                }
+                Instruction::ForEnd => {
+                    let finished = self
+                        .loops
+                        .pop()
+                        .expect("compiled code only ends started loops");
+                    *self.variable(finished.variable) = finished.previous;
+                }
+                Instruction::Add => self.binary(BinaryOp::Add, span)?,
+                Instruction::Sub => self.binary(BinaryOp::Sub, span)?,
+                Instruction::Mul => self.binary(BinaryOp::Mul, span)?,
+                Instruction::Div => self.binary(BinaryOp::Div, span)?,
+                Instruction::Mod => self.binary(BinaryOp::Mod, span)?,
+                Instruction::Pow => self.binary(BinaryOp::Pow, span)?,
+                Instruction::BitAnd => self.binary(BinaryOp::BitAnd, span)?,
+                Instruction::BitOr => self.binary(BinaryOp::BitOr, span)?,
+                Instruction::BitXor => self.binary(BinaryOp::BitXor, span)?,
+                Instruction::Shl => self.binary(BinaryOp::Shl, span)?,
+                Instruction::Shr => self.binary(BinaryOp::Shr, span)?,
+                Instruction::Eq => self.binary(BinaryOp::Eq, span)?,
+                Instruction::Ne => self.binary(BinaryOp::Ne, span)?,
+                Instruction::Lt => self.binary(BinaryOp::Lt, span)?,
+                Instruction::Le => self.binary(BinaryOp::Le, span)?,
+                Instruction::Gt => self.binary(BinaryOp::Gt, span)?,
+                Instruction::Ge => self.binary(BinaryOp::Ge, span)?,
+                Instruction::Not => {
+                    let value = self.pop().as_bool(span)?;
+                    self.stack.push(Value::Bool(!value));
+                }
+                Instruction::Neg => {
+                    let value = self.pop();
+                    self.stack.push(negate(value, self.arithmetic, span)?);
+                }
+                Instruction::BitNot => {
+                    let value = self.pop();
+                    self.stack.push(bit_not(value, span)?);
+                }
+                Instruction::CheckBool => {
+                    self.peek().as_bool(span)?;
+                }
+                Instruction::CheckNumber => check_number(self.peek(), span)?,
+                Instruction::Jump(target) => pc = target,
+                Instruction::JumpIfFalse(target) => {
+                    if !self.pop().as_bool(span)? {
+                        pc = target;
+                    }
+                }
+                Instruction::Define(index) => {
+                    self.function_slots[chunk.functions[index].slot] = Some(index);
+                }
+                Instruction::Call(slot, argc) => {
+                    let args = self.stack.split_off(self.stack.len() - argc);
+                    let name = &chunk.function_names[slot];
+                    let Some(index) = self.function_slots[slot] else {
+                        let result = match self.natives.get(name) {
+                            Some(native) => native.call(name, &args, self.arithmetic, span)?,
+                            None => {
+                                return Err(EvalError::UndefinedFunction {
+                                    name: name.clone(),
+                                    span,
+                                })
+                            }
+                        };
+                        self.stack.push(result);
+                        continue;
+                    };
+                    let function = &chunk.functions[index];
+                    if argc != function.arity {
+                        return Err(EvalError::ArityMismatch {
+                            name: name.clone(),
+                            expected: function.arity,
+                            found: argc,
+                            span,
+                        });
+                    }
+                    if self.frames.len() >= self.max_call_depth {
+                        return Err(EvalError::StackOverflow {
+                            depth: self.max_call_depth,
+                            span,
+                        });
+                    }
+
+                    let mut locals: Vec<Option<Value>> = args.into_iter().map(Some).collect();
+                    locals.resize(function.locals.len(), None);
+                    self.frames.push(CallFrame {
+                        function: index,
+                        return_pc: pc,
+                        locals,
+                        loops: self.loops.len(),
+                    });
+                    pc = function.entry;
+                }
+                Instruction::Return => {
+                    //A return outside of any call ends the program, as in the interpreter
+                    let Some(frame) = self.frames.pop() else {
+                        break;
+                    };
+                    //Loops left by the return end along with the call's locals
+                    self.loops.truncate(frame.loops);
+                    pc = frame.return_pc;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the variables defined by the last run, keyed by name
+    pub fn variables(&self) -> HashMap<String, Value> {
+        self.names
+            .iter()
+            .zip(&self.slots)
+            .filter_map(|(name, value)| Some((name.clone(), value.clone()?)))
+            .collect()
+    }
+
+    /// Returns the names of the functions defined by the last run
+    pub(crate) fn defined_functions(&self) -> impl Iterator<Item = &String> {
+        self.function_names
+            .iter()
+            .zip(&self.function_slots)
+            .filter(|(_, index)| index.is_some())
+            .map(|(name, _)| name)
+    }
+
+    /// Ends the loops and calls an error left in progress, innermost first,
+    /// so loop variables get back the values they had before their loop as
+    /// they do in the interpreter
+    fn unwind(&mut self) {
+        while let Some(finished) = self.loops.pop() {
+            //Calls made inside the loop end before it
+            while self
+                .frames
+                .last()
+                .is_some_and(|frame| frame.loops > self.loops.len())
+            {
+                self.frames.pop();
+            }
+            *self.variable(finished.variable) = finished.previous;
+        }
+        self.frames.clear();
+    }
+
+    /// Starts a `for` loop assigning the values of `iteration` to `variable`
+    fn start_loop(&mut self, iteration: Iteration, variable: Slot) {
+        let previous = self.variable(variable).clone();
+        self.loops.push(ForLoop {
+            iteration,
+            variable,
+            previous,
+        });
+    }
+
+    /// Returns the storage of a global or local variable
+    fn variable(&mut self, slot: Slot) -> &mut Option<Value> {
+        match slot {
+            Slot::Global(slot) => &mut self.slots[slot],
+            Slot::Local(slot) => {
+                &mut self
+                    .frames
+                    .last_mut()
+                    .expect("locals are only used inside functions")
+                    .locals[slot]
+            }
+        }
+    }
+
+    /// Returns the error for reading the undefined variable in `slot`
+    fn undefined(&self, chunk: &Chunk, slot: Slot, span: Span) -> EvalError {
+        let name = match slot {
+            Slot::Global(slot) => &chunk.names[slot],
+            Slot::Local(slot) => &chunk.functions[self.frame().function].locals[slot],
+        };
+        EvalError::UndefinedVariable {
+            name: name.clone(),
+            span,
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames
+            .last()
+            .expect("locals are only used inside functions")
+    }
+
+    fn binary(&mut self, op: BinaryOp, span: Span) -> Result<(), EvalError> {
+        let right = self.pop();
+        let left = self.pop();
+        self.stack
+            .push(binary_op(op, left, right, self.arithmetic, span)?);
+        Ok(())
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack
+            .last()
+            .expect("compiled code never reads an empty stack")
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("compiled code never pops an empty stack")
+    }
+}
@@ -0,0 +1,260 @@
+#![cfg(feature = "bigint")]
+
+use anyhow::Result;
+use tiny_lang_parser::{
+    compile, parse_program, ASTNode, BigInt, EvalError, IntegerDivisionMode, Interpreter,
+    OverflowMode, Value, Vm,
+};
+
+///Test that literals of any length parse
+#[test]
+fn test_big_literals() -> Result<()> {
+    let result = parse_program("x = 123456789012345678901234567890;")?;
+
+    if let ASTNode::Assignment { value, .. } = &result[0].node {
+        let expected: BigInt = "123456789012345678901234567890".parse()?;
+        assert_eq!(value.node, ASTNode::BigNumber(expected));
+    } else {
+        panic!("Expected assignment");
+    }
+
+    //Literals that fit stay plain numbers
+    let result = parse_program("9223372036854775807;")?;
+    assert_eq!(result[0], ASTNode::Number(i64::MAX));
+
+    //Prefixed literals may be big too
+    let result = parse_program("0x1_0000_0000_0000_0000;")?;
+    let expected: BigInt = "18446744073709551616".parse()?;
+    assert_eq!(result[0], ASTNode::BigNumber(expected));
+
+    Ok(())
+}
+
+///Test exact arithmetic that exceeds i64 on both backends
+#[test]
+fn test_exact_arithmetic() -> Result<()> {
+    let code = r#"
+        a = 2;
+        n = 0;
+        while (n < 7) {
+            a = a * a;
+            n = n + 1;
+        }
+        b = a / 18446744073709551616 / 18446744073709551616;
+        c = a - a + 1;
+        d = a > 9223372036854775807;
+    "#;
+    let ast = parse_program(code)?;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&ast)?;
+
+    let expected: BigInt = BigInt::from(2).pow(128);
+    assert_eq!(
+        interpreter.variables.get("a"),
+        Some(&Value::BigInt(expected))
+    );
+    //Results that fit are plain ints again
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(1)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(1)));
+    assert_eq!(interpreter.variables.get("d"), Some(&Value::Bool(true)));
+
+    let mut vm = Vm::new();
+    vm.run(&compile(&ast))?;
+    assert_eq!(vm.variables(), interpreter.variables);
+
+    Ok(())
+}
+
+///Test that other overflow modes still apply to i64 arithmetic
+#[test]
+fn test_promote_is_default() -> Result<()> {
+    let ast = parse_program("x = 9223372036854775807 + 1;")?;
+
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&ast)?;
+    assert_eq!(
+        interpreter.variables.get("x").map(ToString::to_string),
+        Some("9223372036854775808".to_string())
+    );
+
+    interpreter.set_overflow_mode(OverflowMode::Checked);
+    assert!(matches!(
+        interpreter.eval(&ast),
+        Err(EvalError::Overflow { op: "+", .. })
+    ));
+
+    let result = interpreter.eval(&parse_program("x = 99999999999999999999 / (1 - 1);")?);
+    assert!(matches!(result, Err(EvalError::DivisionByZero { .. })));
+
+    Ok(())
+}
+
+///Test that big integers mixed with floats give floats
+#[test]
+fn test_big_float_promotion() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program(
+        "x = 18446744073709551616 * 0.5; y = 18446744073709551616 > 1e19;",
+    )?)?;
+
+    assert_eq!(
+        interpreter.variables.get("x"),
+        Some(&Value::Float(9223372036854775808.0))
+    );
+    assert_eq!(interpreter.variables.get("y"), Some(&Value::Bool(true)));
+
+    Ok(())
+}
+
+///Test exact division of integers outside the range of i64
+#[test]
+fn test_big_exact_division() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_integer_division_mode(IntegerDivisionMode::Exact);
+    interpreter.eval(&parse_program("x = 36893488147419103232 / 2;")?)?;
+    let expected: BigInt = "18446744073709551616".parse()?;
+    assert_eq!(interpreter.variables.get("x"), Some(&Value::from(expected)));
+
+    //Rationals with parts beyond i64 are not representable
+    for code in ["y = x / 3;", "y = x + 1 / 2;"] {
+        let result = interpreter.eval(&parse_program(code)?);
+        assert!(
+            matches!(result, Err(EvalError::Overflow { .. })),
+            "{} should overflow",
+            code
+        );
+    }
+
+    Ok(())
+}
+
+///Test exponentiation and remainder beyond i64
+#[test]
+fn test_big_powers() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_overflow_mode(OverflowMode::Promote);
+    interpreter.eval(&parse_program(
+        "x = 2 ** 100; y = x % 1000; z = -x; w = (-x) ** 2 == 2 ** 200;",
+    )?)?;
+
+    let expected: BigInt = "1267650600228229401496703205376".parse()?;
+    assert_eq!(
+        interpreter.variables.get("x"),
+        Some(&Value::from(expected.clone()))
+    );
+    assert_eq!(interpreter.variables.get("y"), Some(&Value::Int(376)));
+    assert_eq!(
+        interpreter.variables.get("z"),
+        Some(&Value::from(-expected))
+    );
+    assert_eq!(interpreter.variables.get("w"), Some(&Value::Bool(true)));
+
+    //Results too large to hold in memory are reported as overflow
+    let result = interpreter.eval(&parse_program("x = 3 ** 1000000000;")?);
+    assert!(matches!(result, Err(EvalError::Overflow { op: "**", .. })));
+
+    Ok(())
+}
+
+///Test bitwise operators on integers beyond i64
+#[test]
+fn test_big_bitwise() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program(
+        "x = 2 ** 70; a = (x | 1) & 3; b = x >> 63; c = ~x == -x - 1; d = x << 1 == x * 2;",
+    )?)?;
+
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(1)));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(128)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Bool(true)));
+    assert_eq!(interpreter.variables.get("d"), Some(&Value::Bool(true)));
+
+    //Big integers shift by any amount, up to the size limit of `**`
+    interpreter.eval(&parse_program(
+        "e = 2 ** 200 >> 190; f = x >> x; g = -x >> 100; h = 0 << x;",
+    )?)?;
+    assert_eq!(interpreter.variables.get("e"), Some(&Value::Int(1024)));
+    assert_eq!(interpreter.variables.get("f"), Some(&Value::Int(0)));
+    assert_eq!(interpreter.variables.get("g"), Some(&Value::Int(-1)));
+    assert_eq!(interpreter.variables.get("h"), Some(&Value::Int(0)));
+
+    let result = interpreter.eval(&parse_program("y = 1 << x;")?);
+    assert!(matches!(result, Err(EvalError::Overflow { op: "<<", .. })));
+    let result = interpreter.eval(&parse_program("y = x >> -1;")?);
+    assert!(matches!(result, Err(EvalError::InvalidShift { .. })));
+
+    //Ints shifted beyond 64 bits are promoted, by any amount
+    interpreter.eval(&parse_program(
+        "y = 1 << 63; z = -3 << 62; a = 1 << 100; b = 1 >> 100; c = -1 >> 100;",
+    )?)?;
+    assert_eq!(
+        interpreter.variables.get("y"),
+        Some(&Value::from(BigInt::from(1) << 63))
+    );
+    assert_eq!(
+        interpreter.variables.get("z"),
+        Some(&Value::from(BigInt::from(-3) << 62))
+    );
+    assert_eq!(
+        interpreter.variables.get("a"),
+        Some(&Value::from(BigInt::from(1) << 100))
+    );
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(0)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(-1)));
+
+    //Other modes keep the limits of i64
+    interpreter.set_overflow_mode(OverflowMode::Checked);
+    let result = interpreter.eval(&parse_program("y = 1 << 64;")?);
+    assert!(matches!(result, Err(EvalError::InvalidShift { .. })));
+
+    Ok(())
+}
+
+///Test that the standard library accepts integers beyond i64
+#[test]
+fn test_big_stdlib() -> Result<()> {
+    let code = "x = 2 ** 100; a = abs(-x); b = min(x, x + 1); c = max(-x, 3); \
+                d = pow(x, 2); e = isqrt(x); f = max(x, 1.5); g = pow(3, 50);";
+    let ast = parse_program(code)?;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&ast)?;
+    let mut vm = Vm::new();
+    vm.run(&compile(&ast))?;
+
+    let x = BigInt::from(2).pow(100);
+    assert_eq!(vm.variables(), interpreter.variables);
+    assert_eq!(
+        interpreter.variables.get("a"),
+        Some(&Value::from(x.clone()))
+    );
+    assert_eq!(
+        interpreter.variables.get("b"),
+        Some(&Value::from(x.clone()))
+    );
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(3)));
+    assert_eq!(
+        interpreter.variables.get("d"),
+        Some(&Value::from(BigInt::from(2).pow(200)))
+    );
+    assert_eq!(
+        interpreter.variables.get("e"),
+        Some(&Value::from(BigInt::from(2).pow(50)))
+    );
+    assert_eq!(
+        interpreter.variables.get("f"),
+        Some(&Value::Float(2f64.powi(100)))
+    );
+    assert_eq!(
+        interpreter.variables.get("g"),
+        Some(&Value::from(BigInt::from(3).pow(50)))
+    );
+
+    let result = interpreter.eval(&parse_program("y = isqrt(-x);")?);
+    if let Err(EvalError::HostError { message, .. }) = result {
+        assert_eq!(message, "cannot take the square root of a negative number");
+    } else {
+        panic!("Expected HostError");
+    }
+
+    Ok(())
+}
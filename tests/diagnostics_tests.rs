@@ -0,0 +1,243 @@
+use anyhow::Result;
+use tiny_lang_parser::{
+    parse_program, ASTNode, ColorMode, Diagnostic, IntegerDivisionMode, Interpreter, Spanned,
+};
+
+///Test rendering of an undefined variable with a spelling suggestion
+#[test]
+fn test_undefined_variable_suggestion() -> Result<()> {
+    let code = "count = 1;\ntotal = cont + 1;";
+    let ast = parse_program(code)?;
+    let mut interpreter = Interpreter::new();
+    let error = interpreter.eval(&ast).unwrap_err();
+
+    let rendered = interpreter
+        .diagnose(&error)
+        .render(code, "script.tl", ColorMode::Plain);
+    let expected = "\
+error: undefined variable `cont`
+ --> script.tl:2:9
+  |
+2 | total = cont + 1;
+  |         ^^^^ not found in this scope
+  |
+  = help: did you mean `count`?
+";
+    assert_eq!(rendered, expected);
+
+    Ok(())
+}
+
+///Test rendering of a grammar error
+#[test]
+fn test_parse_error_rendering() {
+    let code = "x = 1;\ny = 2 +;";
+    let error = parse_program(code).unwrap_err();
+
+    let rendered = Diagnostic::from(&error).render(code, "script.tl", ColorMode::Plain);
+    assert!(rendered.starts_with("error: invalid syntax\n --> script.tl:2:8\n"));
+    assert!(rendered.contains("2 | y = 2 +;\n  |        ^ expected an expression\n"));
+}
+
+///Test that a statement missing its `;` is reported as such
+#[test]
+fn test_missing_semicolon_rendering() {
+    let code = "x = 1\ny = 2;";
+    let error = parse_program(code).unwrap_err();
+
+    let rendered = Diagnostic::from(&error).render(code, "script.tl", ColorMode::Plain);
+    let expected = "\
+error: invalid syntax
+ --> script.tl:2:1
+  |
+2 | y = 2;
+  | ^ expected one of `;`, an expression or an operator
+  |
+  = help: statements other than functions, `if` and loops end with `;`; check whether the previous one is missing it
+";
+    assert_eq!(rendered, expected);
+}
+
+///Test rendering of a malformed number literal
+#[test]
+fn test_invalid_number_rendering() {
+    let code = "mask = 0b1012;";
+    let error = parse_program(code).unwrap_err();
+
+    let rendered = Diagnostic::from(&error).render(code, "script.tl", ColorMode::Plain);
+    let expected = "\
+error: invalid number literal `0b1012`
+ --> script.tl:1:8
+  |
+1 | mask = 0b1012;
+  |        ^^^^^^ `2` is not a binary digit
+";
+    assert_eq!(rendered, expected);
+}
+
+///Test that a bad escape is reported at the escape itself
+#[test]
+fn test_invalid_escape_rendering() {
+    let code = "s = \"a\\qb\";";
+    let error = parse_program(code).unwrap_err();
+
+    let rendered = Diagnostic::from(&error).render(code, "script.tl", ColorMode::Plain);
+    let expected = "\
+error: invalid escape `\\q` in string literal
+ --> script.tl:1:7
+  |
+1 | s = \"a\\qb\";
+  |       ^^ unknown escape sequence
+  |
+  = help: the supported escapes are \\n, \\t, \\r, \\0, \\\\, \\\", \\{, \\} and \\u{...}
+";
+    assert_eq!(rendered, expected);
+}
+
+///Test that a keyword used as a name is reported with a suggestion
+#[test]
+fn test_reserved_keyword_rendering() {
+    let code = "x = 1;\nwhile = x;";
+    let error = parse_program(code).unwrap_err();
+
+    let rendered = Diagnostic::from(&error).render(code, "script.tl", ColorMode::Plain);
+    let expected = "\
+error: `while` is a reserved keyword
+ --> script.tl:2:1
+  |
+2 | while = x;
+  | ^^^^^ cannot be used as a name
+  |
+  = help: choose another name, such as `while_`
+";
+    assert_eq!(rendered, expected);
+}
+
+///Test that colored mode emits ANSI escapes and plain mode does not
+#[test]
+fn test_color_modes() -> Result<()> {
+    let code = "x = 1 / 0;";
+    let ast = parse_program(code)?;
+    let mut interpreter = Interpreter::new();
+    let error = interpreter.eval(&ast).unwrap_err();
+    let diagnostic = interpreter.diagnose(&error);
+
+    assert!(diagnostic
+        .render(code, "script.tl", ColorMode::Colored)
+        .contains("\x1b[1;31merror\x1b[0m"));
+    assert!(!diagnostic
+        .render(code, "script.tl", ColorMode::Plain)
+        .contains('\x1b'));
+
+    Ok(())
+}
+
+///Test rendering of an index past the end of an array
+#[test]
+fn test_index_out_of_bounds_rendering() -> Result<()> {
+    let code = "xs = [1, 2, 3];\nx = xs[3];";
+    let mut interpreter = Interpreter::new();
+    let error = interpreter.eval(&parse_program(code)?).unwrap_err();
+
+    let rendered = interpreter
+        .diagnose(&error)
+        .render(code, "script.tl", ColorMode::Plain);
+    let expected = "\
+error: index out of range
+ --> script.tl:2:5
+  |
+2 | x = xs[3];
+  |     ^^^^^ index 3 is out of range
+  |
+  = note: the length is 3, so indexes must be between -3 and 2
+";
+    assert_eq!(rendered, expected);
+
+    //A slice may end at the length, so its bounds get their own range
+    let code = "xs = [1, 2, 3];\nys = xs[1:5];";
+    let error = interpreter.eval(&parse_program(code)?).unwrap_err();
+    let rendered = interpreter
+        .diagnose(&error)
+        .render(code, "script.tl", ColorMode::Plain);
+    let expected = "\
+error: slice bound out of range
+ --> script.tl:2:6
+  |
+2 | ys = xs[1:5];
+  |      ^^^^^^^ slice bound 5 is out of range
+  |
+  = note: the length is 3, so slice bounds must be between -3 and 3
+";
+    assert_eq!(rendered, expected);
+
+    Ok(())
+}
+
+///Test that the division by zero note names the operator that divided
+#[test]
+fn test_division_by_zero_rendering() -> Result<()> {
+    let code = "x = 0;\ny = x ** -1;";
+    let mut interpreter = Interpreter::new();
+    interpreter.set_integer_division_mode(IntegerDivisionMode::Exact);
+    let error = interpreter.eval(&parse_program(code)?).unwrap_err();
+
+    let rendered = interpreter
+        .diagnose(&error)
+        .render(code, "script.tl", ColorMode::Plain);
+    let expected = "\
+error: division by zero
+ --> script.tl:2:5
+  |
+2 | y = x ** -1;
+  |     ^^^^^^^ attempt to raise 0 to a negative power
+  |
+  = note: a negative power divides 1 by the base, which evaluated to 0
+";
+    assert_eq!(rendered, expected);
+
+    let code = "y = 1 / 0;";
+    let error = interpreter.eval(&parse_program(code)?).unwrap_err();
+    let rendered = interpreter
+        .diagnose(&error)
+        .render(code, "script.tl", ColorMode::Plain);
+    assert!(rendered.ends_with("= note: the right-hand side of `/` evaluated to 0\n"));
+
+    Ok(())
+}
+
+///Test rendering of a field missing from a map
+#[test]
+fn test_missing_key_rendering() -> Result<()> {
+    let code = "point = { x: 1, y: 2 };\nz = point.z;";
+    let mut interpreter = Interpreter::new();
+    let error = interpreter.eval(&parse_program(code)?).unwrap_err();
+
+    let rendered = interpreter
+        .diagnose(&error)
+        .render(code, "script.tl", ColorMode::Plain);
+    let expected = "\
+error: missing key `z`
+ --> script.tl:2:5
+  |
+2 | z = point.z;
+  |     ^^^^^^^ the map has no key `z`
+  |
+  = help: `keys(map)` lists the keys a map has
+";
+    assert_eq!(rendered, expected);
+
+    Ok(())
+}
+
+///Test that labels without a source position are left out of the snippet
+#[test]
+fn test_unknown_span_rendering() {
+    let ast = vec![Spanned::from(ASTNode::Identifier("x".to_string()))];
+    let mut interpreter = Interpreter::new();
+    let error = interpreter.eval(&ast).unwrap_err();
+
+    let rendered = interpreter
+        .diagnose(&error)
+        .render("", "script.tl", ColorMode::Plain);
+    assert_eq!(rendered, "error: undefined variable `x`\n");
+}
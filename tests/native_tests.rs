@@ -0,0 +1,201 @@
+use anyhow::Result;
+use tiny_lang_parser::{
+    compile, parse_program, EvalError, IntegerDivisionMode, Interpreter, OverflowMode, Rational,
+    Value, Vm,
+};
+
+///Test the standard library registered by default
+#[test]
+fn test_stdlib() -> Result<()> {
+    let code = "a = abs(0 - 7); b = min(3, 9); c = max(3, 9); d = pow(2, 10); e = isqrt(99);";
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program(code)?)?;
+
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(7)));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(3)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(9)));
+    assert_eq!(interpreter.variables.get("d"), Some(&Value::Int(1024)));
+    assert_eq!(interpreter.variables.get("e"), Some(&Value::Int(9)));
+
+    //Failures surface as host errors naming the function
+    interpreter.set_overflow_mode(OverflowMode::Checked);
+    let result = interpreter.eval(&parse_program("f = pow(2, 64);")?);
+    assert!(matches!(result, Err(EvalError::HostError { name, .. }) if name == "pow"));
+
+    let result = interpreter.eval(&parse_program("g = len(5);")?);
+    if let Err(EvalError::HostError { message, .. }) = result {
+        assert_eq!(
+            message,
+            "argument 1 must be a string, array or map, found int"
+        );
+    } else {
+        panic!("Expected HostError");
+    }
+
+    Ok(())
+}
+
+///Test that abs, min and max accept floats and promote mixed arguments
+#[test]
+fn test_stdlib_floats() -> Result<()> {
+    let code =
+        "a = abs(-1.5); b = max(1, 2.5); c = min(1, 2.5); d = max(3, 2.5); e = min(0.5, 0.25);";
+    let ast = parse_program(code)?;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&ast)?;
+    let mut vm = Vm::new();
+    vm.run(&compile(&ast))?;
+
+    assert_eq!(vm.variables(), interpreter.variables);
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Float(1.5)));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Float(2.5)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Float(1.0)));
+    assert_eq!(interpreter.variables.get("d"), Some(&Value::Float(3.0)));
+    assert_eq!(interpreter.variables.get("e"), Some(&Value::Float(0.25)));
+
+    let result = interpreter.eval(&parse_program("f = max(1, true);")?);
+    if let Err(EvalError::HostError { message, .. }) = result {
+        assert_eq!(message, "argument 2 must be a number, found bool");
+    } else {
+        panic!("Expected HostError");
+    }
+
+    Ok(())
+}
+
+///Test that abs, min and max accept the rationals of exact division and
+///compare them exactly
+#[test]
+fn test_stdlib_rationals() -> Result<()> {
+    let code = "a = abs(-1 / 3); b = min(1 / 3, 1 / 4); c = max(-1 / 3, 0); d = min(2 / 3, 1.0); \
+                e = min(1 / 9007199254740992, 1 / 9007199254740993); f = max(1 / 2, 2 / 4);";
+    let ast = parse_program(code)?;
+    let mut interpreter = Interpreter::new();
+    interpreter.set_integer_division_mode(IntegerDivisionMode::Exact);
+    interpreter.eval(&ast)?;
+    let mut vm = Vm::new();
+    vm.set_integer_division_mode(IntegerDivisionMode::Exact);
+    vm.run(&compile(&ast))?;
+
+    let rational = |n, d| Rational::new(n, d).map(Value::Rational);
+    assert_eq!(vm.variables(), interpreter.variables);
+    assert_eq!(interpreter.variables.get("a"), rational(1, 3).as_ref());
+    assert_eq!(interpreter.variables.get("b"), rational(1, 4).as_ref());
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(0)));
+    assert_eq!(
+        interpreter.variables.get("d"),
+        Some(&Value::Float(2.0 / 3.0))
+    );
+    //The two are equal as floats
+    assert_eq!(
+        interpreter.variables.get("e"),
+        rational(1, 9007199254740993).as_ref()
+    );
+    assert_eq!(interpreter.variables.get("f"), rational(1, 2).as_ref());
+
+    Ok(())
+}
+
+///Test that abs and pow follow the overflow mode like the operators do
+#[test]
+fn test_stdlib_overflow_modes() -> Result<()> {
+    let code = "a = abs(0 - 9223372036854775807 - 1); b = pow(2, 64); c = pow(0 - 3, 41);";
+    let ast = parse_program(code)?;
+    for (mode, a, b, c) in [
+        (
+            OverflowMode::Wrapping,
+            i64::MIN,
+            0,
+            (-3i64).wrapping_pow(41),
+        ),
+        (OverflowMode::Saturating, i64::MAX, i64::MAX, i64::MIN),
+    ] {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_overflow_mode(mode);
+        interpreter.eval(&ast)?;
+        let mut vm = Vm::new();
+        vm.set_overflow_mode(mode);
+        vm.run(&compile(&ast))?;
+
+        assert_eq!(vm.variables(), interpreter.variables);
+        assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(a)));
+        assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(b)));
+        assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(c)));
+    }
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_overflow_mode(OverflowMode::Checked);
+    let result = interpreter.eval(&parse_program("a = abs(0 - 9223372036854775807 - 1);")?);
+    assert!(matches!(result, Err(EvalError::HostError { name, .. }) if name == "abs"));
+
+    Ok(())
+}
+
+///Test registering host functions with arity checking and fallible results
+#[test]
+fn test_register_fn() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.register_fn("clamp", 3, |args| match args {
+        [Value::Int(x), Value::Int(lo), Value::Int(hi)] if lo <= hi => {
+            Ok(Value::Int(*x.max(lo).min(hi)))
+        }
+        [Value::Int(_), Value::Int(_), Value::Int(_)] => Err("empty range".to_string()),
+        _ => Err("expected three ints".to_string()),
+    });
+
+    interpreter.eval(&parse_program("x = clamp(15, 0, 10);")?)?;
+    assert_eq!(interpreter.variables.get("x"), Some(&Value::Int(10)));
+
+    let result = interpreter.eval(&parse_program("x = clamp(1, 2);")?);
+    assert!(matches!(
+        result,
+        Err(EvalError::ArityMismatch {
+            expected: 3,
+            found: 2,
+            ..
+        })
+    ));
+
+    let result = interpreter.eval(&parse_program("x = clamp(1, 5, 0);")?);
+    if let Err(EvalError::HostError { message, span, .. }) = result {
+        assert_eq!(message, "empty range");
+        assert_eq!(span.column, 5);
+    } else {
+        panic!("Expected HostError");
+    }
+
+    //Script functions take precedence over native ones
+    interpreter.eval(&parse_program(
+        "fn clamp(a, b, c) { return 0; } y = clamp(5, 1, 9);",
+    )?)?;
+    assert_eq!(interpreter.variables.get("y"), Some(&Value::Int(0)));
+
+    Ok(())
+}
+
+///Test that native functions can be removed for sandboxed scripts
+#[test]
+fn test_sandboxed_natives() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    assert!(interpreter.unregister_fn("pow"));
+    assert!(!interpreter.unregister_fn("pow"));
+    let result = interpreter.eval(&parse_program("x = pow(2, 2);")?);
+    assert!(matches!(result, Err(EvalError::UndefinedFunction { .. })));
+
+    let interpreter = Interpreter::without_stdlib();
+    assert_eq!(interpreter.native_functions().count(), 0);
+
+    let mut vm = Vm::without_stdlib();
+    vm.register_fn("answer", 0, |_| Ok(Value::Int(42)));
+    let chunk = compile(&parse_program("x = answer();")?);
+    vm.run(&chunk)?;
+    assert_eq!(vm.variables().get("x"), Some(&Value::Int(42)));
+
+    let chunk = compile(&parse_program("x = abs(1);")?);
+    assert!(matches!(
+        vm.run(&chunk),
+        Err(EvalError::UndefinedFunction { .. })
+    ));
+
+    Ok(())
+}
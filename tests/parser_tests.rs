@@ -1,5 +1,9 @@
 use anyhow::Result;
-use tiny_lang_parser::{parse_program, ASTNode, EvalError, Interpreter};
+use tiny_lang_parser::{
+    parse_program, parse_program_recovering, ASTNode, BufferSink, EvalError, FloatDivisionMode,
+    IntegerDivisionMode, Interpreter, OverflowMode, ParseError, Rational, Value,
+    DEFAULT_MAX_CALL_DEPTH, KEYWORDS,
+};
 
 ///Test grammar rule: program
 #[test]
@@ -20,9 +24,9 @@ fn test_program_rule() -> Result<()> {
 fn test_assignment_rule() -> Result<()> {
     let result = parse_program("answer = 42;")?;
 
-    if let ASTNode::Assignment { name, value } = &result[0] {
+    if let ASTNode::Assignment { name, value, .. } = &result[0].node {
         assert_eq!(name, "answer");
-        assert!(matches!(**value, ASTNode::Number(42)));
+        assert!(matches!(value.node, ASTNode::Number(42)));
     } else {
         panic!("Expected assignment node");
     }
@@ -37,11 +41,11 @@ fn test_expression_rule() -> Result<()> {
     let result = parse_program("2 + 3 * 4;")?;
 
     //Should parse as 2 + (3 * 4), not (2 + 3) * 4
-    if let ASTNode::Add(left, right) = &result[0] {
-        assert!(matches!(**left, ASTNode::Number(2)));
-        if let ASTNode::Mul(l, r) = &**right {
-            assert!(matches!(**l, ASTNode::Number(3)));
-            assert!(matches!(**r, ASTNode::Number(4)));
+    if let ASTNode::Add(left, right) = &result[0].node {
+        assert!(matches!(left.node, ASTNode::Number(2)));
+        if let ASTNode::Mul(l, r) = &right.node {
+            assert!(matches!(l.node, ASTNode::Number(3)));
+            assert!(matches!(r.node, ASTNode::Number(4)));
         } else {
             panic!("Expected multiplication in right operand");
         }
@@ -59,11 +63,11 @@ fn test_factor_rule() -> Result<()> {
     let result = parse_program("(2 + 3) * 4;")?;
 
     //Should parse as (2 + 3) * 4
-    if let ASTNode::Mul(left, right) = &result[0] {
-        assert!(matches!(**right, ASTNode::Number(4)));
-        if let ASTNode::Add(l, r) = &**left {
-            assert!(matches!(**l, ASTNode::Number(2)));
-            assert!(matches!(**r, ASTNode::Number(3)));
+    if let ASTNode::Mul(left, right) = &result[0].node {
+        assert!(matches!(right.node, ASTNode::Number(4)));
+        if let ASTNode::Add(l, r) = &left.node {
+            assert!(matches!(l.node, ASTNode::Number(2)));
+            assert!(matches!(r.node, ASTNode::Number(3)));
         } else {
             panic!("Expected addition in left operand");
         }
@@ -74,6 +78,167 @@ fn test_factor_rule() -> Result<()> {
     Ok(())
 }
 
+///Test grammar rule: term with remainder
+#[test]
+fn test_modulo_rule() -> Result<()> {
+    //Test that remainder has the precedence of multiplication
+    let result = parse_program("1 + 10 % 4 * 3;")?;
+
+    //Should parse as 1 + ((10 % 4) * 3)
+    if let ASTNode::Add(left, right) = &result[0].node {
+        assert!(matches!(left.node, ASTNode::Number(1)));
+        if let ASTNode::Mul(l, r) = &right.node {
+            assert!(matches!(l.node, ASTNode::Mod(_, _)));
+            assert!(matches!(r.node, ASTNode::Number(3)));
+        } else {
+            panic!("Expected multiplication in right operand");
+        }
+    } else {
+        panic!("Expected addition node");
+    }
+
+    Ok(())
+}
+
+///Test grammar rule: power
+#[test]
+fn test_power_rule() -> Result<()> {
+    //Test that exponentiation has higher precedence than multiplication
+    let result = parse_program("2 * 3 ** 2;")?;
+
+    //Should parse as 2 * (3 ** 2), not (2 * 3) ** 2
+    if let ASTNode::Mul(left, right) = &result[0].node {
+        assert!(matches!(left.node, ASTNode::Number(2)));
+        if let ASTNode::Pow(l, r) = &right.node {
+            assert!(matches!(l.node, ASTNode::Number(3)));
+            assert!(matches!(r.node, ASTNode::Number(2)));
+        } else {
+            panic!("Expected exponentiation in right operand");
+        }
+    } else {
+        panic!("Expected multiplication node");
+    }
+
+    //Test right associativity: 2 ** (3 ** 2)
+    let result = parse_program("2 ** 3 ** 2;")?;
+    if let ASTNode::Pow(left, right) = &result[0].node {
+        assert!(matches!(left.node, ASTNode::Number(2)));
+        assert!(matches!(right.node, ASTNode::Pow(_, _)));
+    } else {
+        panic!("Expected exponentiation node");
+    }
+
+    //Test that a negative exponent needs no parentheses
+    let result = parse_program("2 ** -1;")?;
+    if let ASTNode::Pow(_, right) = &result[0].node {
+        assert!(matches!(right.node, ASTNode::Neg(_)));
+    } else {
+        panic!("Expected exponentiation node");
+    }
+
+    assert!(parse_program("2 ** ;").is_err());
+    assert!(parse_program("2 * * 3;").is_err());
+
+    Ok(())
+}
+
+///Test grammar rule: unary with negation and plus
+#[test]
+fn test_unary_minus_rule() -> Result<()> {
+    //Test that negation binds tighter than multiplication
+    let result = parse_program("-x * y;")?;
+
+    //Should parse as (-x) * y
+    if let ASTNode::Mul(left, right) = &result[0].node {
+        assert!(matches!(left.node, ASTNode::Neg(_)));
+        assert!(matches!(right.node, ASTNode::Identifier(_)));
+    } else {
+        panic!("Expected multiplication node");
+    }
+
+    //Test that exponentiation binds tighter than negation: -(2 ** 2)
+    let result = parse_program("-2 ** 2;")?;
+    if let ASTNode::Neg(operand) = &result[0].node {
+        assert!(matches!(operand.node, ASTNode::Pow(_, _)));
+    } else {
+        panic!("Expected negation node");
+    }
+
+    //Test stacked prefix operators and subtraction of a negation
+    let result = parse_program("a - -+b;")?;
+    if let ASTNode::Sub(_, right) = &result[0].node {
+        if let ASTNode::Neg(operand) = &right.node {
+            assert!(matches!(operand.node, ASTNode::Plus(_)));
+        } else {
+            panic!("Expected negation in right operand");
+        }
+    } else {
+        panic!("Expected subtraction node");
+    }
+
+    //i64::MIN can be written as a literal, whose magnitude alone does not fit
+    for code in ["-9223372036854775808;", "-0x8000_0000_0000_0000;"] {
+        let result = parse_program(code)?;
+        assert_eq!(result[0].node, ASTNode::Number(i64::MIN), "{}", code);
+    }
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program("x = -9223372036854775808;")?)?;
+    assert_eq!(interpreter.variables.get("x"), Some(&Value::Int(i64::MIN)));
+    #[cfg(not(feature = "bigint"))]
+    assert!(matches!(
+        parse_program("-(9223372036854775808);"),
+        Err(ParseError::InvalidNumber { .. })
+    ));
+
+    Ok(())
+}
+
+///Test grammar rules: bit_or, bit_xor, bit_and and shift
+#[test]
+fn test_bitwise_rules() -> Result<()> {
+    //Test C precedence: | below ^ below & below ==
+    let result = parse_program("a | b ^ c & d == e;")?;
+
+    //Should parse as a | (b ^ (c & (d == e)))
+    if let ASTNode::BitOr(left, right) = &result[0].node {
+        assert!(matches!(left.node, ASTNode::Identifier(_)));
+        if let ASTNode::BitXor(_, r) = &right.node {
+            if let ASTNode::BitAnd(_, r) = &r.node {
+                assert!(matches!(r.node, ASTNode::Eq(_, _)));
+            } else {
+                panic!("Expected bitwise and in right operand");
+            }
+        } else {
+            panic!("Expected bitwise xor in right operand");
+        }
+    } else {
+        panic!("Expected bitwise or node");
+    }
+
+    //Test that shifts bind looser than addition but tighter than comparison
+    let result = parse_program("1 << n + 1 < m;")?;
+    if let ASTNode::Lt(left, _) = &result[0].node {
+        if let ASTNode::Shl(_, r) = &left.node {
+            assert!(matches!(r.node, ASTNode::Add(_, _)));
+        } else {
+            panic!("Expected shift in left operand");
+        }
+    } else {
+        panic!("Expected comparison node");
+    }
+
+    //Test that single & and | do not swallow the logical operators
+    let result = parse_program("a & b && c | d || ~e;")?;
+    if let ASTNode::Or(left, right) = &result[0].node {
+        assert!(matches!(left.node, ASTNode::And(_, _)));
+        assert!(matches!(right.node, ASTNode::BitNot(_)));
+    } else {
+        panic!("Expected logical or node");
+    }
+
+    Ok(())
+}
+
 ///Test interpreter execution
 #[test]
 fn test_interpreter() -> Result<()> {
@@ -87,9 +252,9 @@ fn test_interpreter() -> Result<()> {
     let mut interpreter = Interpreter::new();
     interpreter.eval(&ast)?;
 
-    assert_eq!(interpreter.variables.get("x"), Some(&10));
-    assert_eq!(interpreter.variables.get("y"), Some(&5));
-    assert_eq!(interpreter.variables.get("z"), Some(&20));
+    assert_eq!(interpreter.variables.get("x"), Some(&Value::Int(10)));
+    assert_eq!(interpreter.variables.get("y"), Some(&Value::Int(5)));
+    assert_eq!(interpreter.variables.get("z"), Some(&Value::Int(20)));
 
     Ok(())
 }
@@ -104,8 +269,9 @@ fn test_undefined_variable() -> Result<()> {
 
     assert!(result.is_err());
 
-    if let Err(EvalError::UndefinedVariable(var_name)) = result {
-        assert_eq!(var_name, "undefined");
+    if let Err(EvalError::UndefinedVariable { name, span }) = result {
+        assert_eq!(name, "undefined");
+        assert_eq!((span.line, span.column), (1, 10));
     } else {
         panic!("Expected UndefinedVariable error");
     }
@@ -123,8 +289,9 @@ fn test_division_by_zero() -> Result<()> {
 
     assert!(result.is_err());
 
-    if let Err(EvalError::DivisionByZero) = result {
-        //Expected error
+    if let Err(EvalError::DivisionByZero { op, span }) = result {
+        assert_eq!(op, "/");
+        assert_eq!(&code[span.start..span.end], "5 / 0");
     } else {
         panic!("Expected DivisionByZero error");
     }
@@ -145,9 +312,1661 @@ fn test_complex_expression() -> Result<()> {
     let mut interpreter = Interpreter::new();
     interpreter.eval(&ast)?;
 
-    assert_eq!(interpreter.variables.get("a"), Some(&10));
-    assert_eq!(interpreter.variables.get("b"), Some(&2));
-    assert_eq!(interpreter.variables.get("c"), Some(&34));
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(10)));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(2)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(34)));
+
+    Ok(())
+}
+
+///Test error recovery: every broken statement is reported
+#[test]
+fn test_recovering_parse() {
+    let code = "x = 1;\ny = 2 +;\nz = (3;\nw = x * 4;";
+    let (ast, errors) = parse_program_recovering(code);
+
+    //Valid statements around the broken ones are kept
+    assert_eq!(ast.len(), 2);
+    assert!(matches!(&ast[0].node, ASTNode::Assignment { name, .. } if name == "x"));
+    assert!(matches!(&ast[1].node, ASTNode::Assignment { name, .. } if name == "w"));
+
+    //Errors point into the original source
+    let lines: Vec<usize> = errors
+        .iter()
+        .map(|e| match e {
+            ParseError::PestError(e) => match e.line_col {
+                pest::error::LineColLocation::Pos((line, _)) => line,
+                pest::error::LineColLocation::Span((line, _), _) => line,
+            },
+            other => panic!("Expected grammar error, got {other:?}"),
+        })
+        .collect();
+    assert_eq!(lines, vec![2, 3]);
+}
+
+///Test that recovery on a valid program matches the regular parser
+#[test]
+fn test_recovering_parse_valid_program() -> Result<()> {
+    let code = "a = 2; b = a * a; b - 1;";
+    let (ast, errors) = parse_program_recovering(code);
+
+    assert!(errors.is_empty());
+    assert_eq!(ast, parse_program(code)?);
+
+    Ok(())
+}
+
+///Test grammar rules: conjunction, equality and comparison precedence
+#[test]
+fn test_logical_precedence() -> Result<()> {
+    //Should parse as (a < b + 1) && (c == d), not a < (b + 1 && c) == d
+    let result = parse_program("a < b + 1 && c == d;")?;
+
+    if let ASTNode::And(left, right) = &result[0].node {
+        if let ASTNode::Lt(_, sum) = &left.node {
+            assert!(matches!(sum.node, ASTNode::Add(_, _)));
+        } else {
+            panic!("Expected comparison in left operand");
+        }
+        assert!(matches!(right.node, ASTNode::Eq(_, _)));
+    } else {
+        panic!("Expected logical and node");
+    }
+
+    //|| binds looser than &&, and ! binds tighter than both
+    let result = parse_program("!a || b && c;")?;
+    if let ASTNode::Or(left, right) = &result[0].node {
+        assert!(matches!(left.node, ASTNode::Not(_)));
+        assert!(matches!(right.node, ASTNode::And(_, _)));
+    } else {
+        panic!("Expected logical or node");
+    }
+
+    Ok(())
+}
+
+///Test evaluation of comparison and boolean operators
+#[test]
+fn test_boolean_evaluation() -> Result<()> {
+    let code = r#"
+        x = 7;
+        small = x < 10;
+        exact = x == 7 && !(x != 7);
+        either = x >= 100 || true;
+        skipped = false && missing;
+    "#;
+
+    let ast = parse_program(code)?;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&ast)?;
+
+    assert_eq!(interpreter.variables.get("small"), Some(&Value::Bool(true)));
+    assert_eq!(interpreter.variables.get("exact"), Some(&Value::Bool(true)));
+    assert_eq!(
+        interpreter.variables.get("either"),
+        Some(&Value::Bool(true))
+    );
+    //The right operand of && is not evaluated when the left one is false
+    assert_eq!(
+        interpreter.variables.get("skipped"),
+        Some(&Value::Bool(false))
+    );
+
+    Ok(())
+}
+
+///Test type errors for mismatched operands
+#[test]
+fn test_type_errors() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+
+    let result = interpreter.eval(&parse_program("x = 1 + true;")?);
+    assert!(matches!(
+        result,
+        Err(EvalError::TypeMismatch {
+            op: "+",
+            left: "int",
+            right: "bool",
+            ..
+        })
+    ));
+
+    let code = "y = !5;";
+    let result = interpreter.eval(&parse_program(code)?);
+    if let Err(EvalError::TypeError {
+        expected,
+        found,
+        span,
+    }) = result
+    {
+        assert_eq!((expected, found), ("bool", "int"));
+        assert_eq!(&code[span.start..span.end], "5");
+    } else {
+        panic!("Expected TypeError");
+    }
+
+    Ok(())
+}
+
+///Test grammar rule: if_statement with an else-if chain
+#[test]
+fn test_if_statement_rule() -> Result<()> {
+    let result = parse_program("if (x < 0) { y = 0; } else if (x > 9) { y = 9; } else { y = x; }")?;
+
+    if let ASTNode::If {
+        condition,
+        then_branch,
+        else_branch,
+    } = &result[0].node
+    {
+        assert!(matches!(condition.node, ASTNode::Lt(_, _)));
+        assert_eq!(then_branch.len(), 1);
+        //else if is a single nested conditional
+        let else_branch = else_branch.as_ref().expect("Expected else branch");
+        assert!(matches!(
+            &else_branch[..],
+            [nested] if matches!(&nested.node, ASTNode::If { else_branch: Some(_), .. })
+        ));
+    } else {
+        panic!("Expected if node");
+    }
+
+    Ok(())
+}
+
+///Test that only the taken branch is executed
+#[test]
+fn test_if_execution() -> Result<()> {
+    let code = r#"
+        x = 15;
+        if (x > 10) {
+            size = 2;
+        } else {
+            size = missing;
+        }
+        if (x == 0) { zero = true; }
+    "#;
+
+    let ast = parse_program(code)?;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&ast)?;
+
+    assert_eq!(interpreter.variables.get("size"), Some(&Value::Int(2)));
+    assert_eq!(interpreter.variables.get("zero"), None);
+
+    //Conditions must be booleans
+    let result = interpreter.eval(&parse_program("if (x) { y = 1; }")?);
+    assert!(matches!(
+        result,
+        Err(EvalError::TypeError {
+            expected: "bool",
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+///Test that recovery skips a broken conditional as a whole
+#[test]
+fn test_recovering_parse_blocks() {
+    let code = "if (x) { y = ; } else { y = 1; }\nz = 2;";
+    let (ast, errors) = parse_program_recovering(code);
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(ast.len(), 1);
+    assert!(matches!(&ast[0].node, ASTNode::Assignment { name, .. } if name == "z"));
+}
+
+///Test that recovery skips strings and comments whole, whatever they contain
+#[test]
+fn test_recovering_parse_strings() {
+    let code = "x = \"a;b\\\"}\" + ; y = 2;\nz = /* ; } */ 1 + // ;\n; w = 3;";
+    let (ast, errors) = parse_program_recovering(code);
+
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert_eq!(ast.len(), 2);
+    assert!(matches!(&ast[0].node, ASTNode::Assignment { name, .. } if name == "y"));
+    assert!(matches!(&ast[1].node, ASTNode::Assignment { name, .. } if name == "w"));
+    //The error is about the missing operand, not the rest of the string
+    if let ParseError::PestError(e) = &errors[0] {
+        assert_eq!(e.line_col, pest::error::LineColLocation::Pos((1, 16)));
+    } else {
+        panic!("Expected grammar error, got {:?}", errors[0]);
+    }
+}
+
+///Test that a broken interpolation is reported once, along with its string
+#[test]
+fn test_recovering_parse_interpolation() {
+    let (ast, errors) = parse_program_recovering("x = \"{1 +}\";\ny = 1;");
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert_eq!(ast.len(), 1);
+    assert!(matches!(&ast[0].node, ASTNode::Assignment { name, .. } if name == "y"));
+
+    //Strings nested in an interpolation do not end the outer string
+    let (ast, errors) = parse_program_recovering("x = \"{f(\"a;b\")}\" + ; y = 1;");
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert_eq!(ast.len(), 1);
+}
+
+///Test that a loop replaces the hand-unrolled powers example
+#[test]
+fn test_while_loop() -> Result<()> {
+    let code = std::fs::read_to_string("test_data/powers_loop.txt")?;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program(&code)?)?;
+
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(65536)));
+    assert_eq!(interpreter.variables.get("f"), Some(&Value::Int(32767)));
+
+    //break leaves the loop, continue skips the rest of the body
+    let code = r#"
+        i = 0;
+        odd = 0;
+        while (true) {
+            i = i + 1;
+            if (i > 9) { break; }
+            if (i / 2 * 2 == i) { continue; }
+            odd = odd + i;
+        }
+    "#;
+    interpreter.eval(&parse_program(code)?)?;
+
+    assert_eq!(interpreter.variables.get("i"), Some(&Value::Int(10)));
+    assert_eq!(interpreter.variables.get("odd"), Some(&Value::Int(25)));
+
+    Ok(())
+}
+
+///Test that break and continue outside of a loop are rejected at parse time
+#[test]
+fn test_loop_control_outside_loop() {
+    let result = parse_program("x = 1;\nif (x == 1) { break; }");
+    if let Err(ParseError::LoopControlOutsideLoop { keyword, span }) = result {
+        assert_eq!(keyword, "break");
+        assert_eq!((span.line, span.column), (2, 15));
+    } else {
+        panic!("Expected LoopControlOutsideLoop error");
+    }
+
+    let (ast, errors) = parse_program_recovering("continue;\nwhile (false) { continue; }");
+    assert_eq!(ast.len(), 1);
+    assert!(matches!(
+        errors[..],
+        [ParseError::LoopControlOutsideLoop {
+            keyword: "continue",
+            ..
+        }]
+    ));
+}
+
+///Test grammar rules: for_statement and range
+#[test]
+fn test_for_rule() -> Result<()> {
+    let result =
+        parse_program("for i in 0..n + 1 { x = i; } for c in name { } for k in 1..=3 { }")?;
+
+    if let ASTNode::For {
+        variable,
+        iterable,
+        body,
+    } = &result[0].node
+    {
+        assert_eq!(variable, "i");
+        assert_eq!(body.len(), 1);
+        if let ASTNode::Range {
+            start,
+            end,
+            inclusive,
+        } = &iterable.node
+        {
+            assert_eq!(start.node, ASTNode::Number(0));
+            assert!(matches!(end.node, ASTNode::Add(..)));
+            assert!(!inclusive);
+        } else {
+            panic!("Expected Range");
+        }
+    } else {
+        panic!("Expected For");
+    }
+
+    assert!(matches!(
+        &result[1].node,
+        ASTNode::For { iterable, .. } if iterable.node == ASTNode::Identifier("name".to_string())
+    ));
+    assert!(matches!(
+        &result[2].node,
+        ASTNode::For { iterable, .. }
+            if matches!(iterable.node, ASTNode::Range { inclusive: true, .. })
+    ));
+
+    //Names merely starting with the keywords are identifiers
+    assert!(matches!(
+        parse_program("format = 1; inner = 2;")?[1].node,
+        ASTNode::Assignment { .. }
+    ));
+    assert!(parse_program("for i in 0.. { }").is_err());
+    assert!(parse_program("for (i in xs) { }").is_err());
+
+    Ok(())
+}
+
+///Test that a for loop replaces the counter of the powers example
+#[test]
+fn test_for_loop() -> Result<()> {
+    let code = std::fs::read_to_string("test_data/powers_for.txt")?;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program(&code)?)?;
+
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(65536)));
+    assert_eq!(interpreter.variables.get("f"), Some(&Value::Int(32767)));
+    //The loop variable does not outlive the loop
+    assert_eq!(interpreter.variables.get("n"), None);
+
+    let code = r#"
+        total = 0;
+        for i in 0..5 { total = total + i; }
+        inclusive = 0;
+        for i in 1..=5 { inclusive = inclusive + i; }
+        empty = 0;
+        for i in 5..0 { empty = empty + 1; }
+        reversed = "";
+        for c in "abc" { reversed = c + reversed; }
+        names = "";
+        for k in { b: 2, a: 1 } { names = names + k; }
+        x = 7;
+        for x in [1, 2, 3] { last = x; }
+        odd = 0;
+        for n in 0..100 {
+            if (n > 9) { break; }
+            if (n % 2 == 0) { continue; }
+            odd = odd + n;
+        }
+        pairs = 0;
+        for a in 0..3 {
+            for b in a..3 { pairs = pairs + 1; }
+        }
+        //The collection is read once, before the first iteration
+        xs = [1, 2];
+        seen = 0;
+        for x in xs { xs[1] = 10; seen = seen + x; }
+    "#;
+    interpreter.eval(&parse_program(code)?)?;
+
+    let get = |name: &str| interpreter.variables.get(name).cloned();
+    assert_eq!(get("total"), Some(Value::Int(10)));
+    assert_eq!(get("inclusive"), Some(Value::Int(15)));
+    assert_eq!(get("empty"), Some(Value::Int(0)));
+    assert_eq!(get("reversed"), Some(Value::from("cba")));
+    assert_eq!(get("names"), Some(Value::from("ab")));
+    assert_eq!(get("last"), Some(Value::Int(3)));
+    assert_eq!(get("odd"), Some(Value::Int(25)));
+    assert_eq!(get("pairs"), Some(Value::Int(6)));
+    assert_eq!(get("seen"), Some(Value::Int(3)));
+    //A variable shadowed by the loop gets its value back
+    assert_eq!(get("x"), Some(Value::Int(7)));
+    assert_eq!(get("i"), None);
+
+    for code in [
+        "for i in 0..1.5 { }",
+        r#"for i in "a".."c" { }"#,
+        "for x in 5 { }",
+        "for x in true { }",
+    ] {
+        let error = Interpreter::new().eval(&parse_program(code)?).unwrap_err();
+        assert!(
+            matches!(error, EvalError::TypeError { .. }),
+            "{}: {:?}",
+            code,
+            error
+        );
+    }
+
+    Ok(())
+}
+
+///Test grammar rules: function_def, return_statement and call
+#[test]
+fn test_function_rules() -> Result<()> {
+    let result = parse_program("fn add(a, b) { return a + b; } x = add(1, add(2, 3));")?;
+
+    if let ASTNode::FunctionDef { name, params, body } = &result[0].node {
+        assert_eq!(name, "add");
+        assert_eq!(params, &["a", "b"]);
+        assert!(matches!(&body[0].node, ASTNode::Return(Some(_))));
+    } else {
+        panic!("Expected function definition");
+    }
+    if let ASTNode::Assignment { value, .. } = &result[1].node {
+        assert!(
+            matches!(&value.node, ASTNode::Call { name, args } if name == "add" && args.len() == 2)
+        );
+    } else {
+        panic!("Expected assignment");
+    }
+
+    //Keywords need a word boundary
+    let result = parse_program("fnord = 1; returned = fnord;")?;
+    assert!(matches!(&result[1].node, ASTNode::Assignment { name, .. } if name == "returned"));
+
+    Ok(())
+}
+
+///Test that misplaced returns, nested functions and repeated parameters are rejected
+#[test]
+fn test_function_validation() {
+    assert!(matches!(
+        parse_program("x = 1;\nreturn x;"),
+        Err(ParseError::ReturnOutsideFunction { .. })
+    ));
+    assert!(matches!(
+        parse_program("if (true) { fn f() { return 1; } }"),
+        Err(ParseError::NestedFunction { name, .. }) if name == "f"
+    ));
+    assert!(matches!(
+        parse_program("fn f(a, b, a) { return a; }"),
+        Err(ParseError::DuplicateParameter { name, .. }) if name == "a"
+    ));
+    //A loop outside the function does not allow break inside it
+    assert!(matches!(
+        parse_program("fn f() { break; }"),
+        Err(ParseError::LoopControlOutsideLoop { .. })
+    ));
+}
+
+///Test function calls with recursion, local scopes and call errors
+#[test]
+fn test_function_calls() -> Result<()> {
+    let code = std::fs::read_to_string("test_data/functions.txt")?;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program(&code)?)?;
+
+    assert_eq!(interpreter.variables.get("x"), Some(&Value::Int(3628800)));
+    assert_eq!(interpreter.variables.get("y"), Some(&Value::Int(6765)));
+    //Parameters and assigned names are local to the call
+    assert_eq!(interpreter.variables.get("n"), None);
+    assert_eq!(interpreter.variables.get("next"), None);
+    assert!(interpreter.functions.contains_key("fact"));
+
+    //Other names read globals; a function without return yields unit
+    let code = "scale = 3; fn times(v) { return v * scale; } fn nothing() { } a = times(2); b = nothing();";
+    interpreter.eval(&parse_program(code)?)?;
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(6)));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Unit));
+
+    let result = interpreter.eval(&parse_program("c = times(1, 2);")?);
+    assert!(matches!(
+        result,
+        Err(EvalError::ArityMismatch {
+            expected: 1,
+            found: 2,
+            ..
+        })
+    ));
+
+    let result = interpreter.eval(&parse_program("c = tims(1);")?);
+    assert!(matches!(result, Err(EvalError::UndefinedFunction { name, .. }) if name == "tims"));
+
+    //Recursion is bounded by the configurable call depth
+    interpreter.set_max_call_depth(10);
+    let code = "fn down(n) { if (n == 0) { return 0; } return down(n - 1); }";
+    interpreter.eval(&parse_program(code)?)?;
+    interpreter.eval(&parse_program("d = down(9);")?)?;
+    let result = interpreter.eval(&parse_program("d = down(10);")?);
+    assert!(matches!(
+        result,
+        Err(EvalError::StackOverflow { depth: 10, .. })
+    ));
+
+    Ok(())
+}
+
+///Test that recursion reaches the default call depth without overflowing the stack
+#[test]
+fn test_default_call_depth() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    let code = "fn count(n) { if (n == 0) { return 0; } return count(n - 1) + 1; }";
+    interpreter.eval(&parse_program(code)?)?;
+
+    //The outermost call is the first of the allowed nested calls
+    let code = format!("r = count({});", DEFAULT_MAX_CALL_DEPTH - 1);
+    interpreter.eval(&parse_program(&code)?)?;
+    assert_eq!(
+        interpreter.variables.get("r"),
+        Some(&Value::Int(DEFAULT_MAX_CALL_DEPTH as i64 - 1))
+    );
+
+    let code = format!("r = count({});", DEFAULT_MAX_CALL_DEPTH);
+    let result = interpreter.eval(&parse_program(&code)?);
+    assert!(matches!(
+        result,
+        Err(EvalError::StackOverflow { depth, .. }) if depth == DEFAULT_MAX_CALL_DEPTH
+    ));
+
+    Ok(())
+}
+
+///Test checked, wrapping and saturating integer overflow
+#[test]
+fn test_overflow_modes() -> Result<()> {
+    let max = "big = 9223372036854775807;";
+    let min = "small = 0 - 9223372036854775807 - 1;";
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_overflow_mode(OverflowMode::Checked);
+    interpreter.eval(&parse_program(&format!("{} {}", max, min))?)?;
+    for (code, op) in [
+        ("x = big + 1;", "+"),
+        ("x = small - 1;", "-"),
+        ("x = big * 2;", "*"),
+        ("x = small / (0 - 1);", "/"),
+    ] {
+        let result = interpreter.eval(&parse_program(code)?);
+        assert!(
+            matches!(result, Err(EvalError::Overflow { op: found, .. }) if found == op),
+            "{} should overflow",
+            code
+        );
+    }
+
+    let code = format!(
+        "{} {} a = big + 1; b = small - 1; c = big * 2; d = small / (0 - 1);",
+        max, min
+    );
+    let ast = parse_program(&code)?;
+
+    interpreter.set_overflow_mode(OverflowMode::Wrapping);
+    interpreter.eval(&ast)?;
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(i64::MIN)));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(i64::MAX)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(-2)));
+    assert_eq!(interpreter.variables.get("d"), Some(&Value::Int(i64::MIN)));
+
+    interpreter.set_overflow_mode(OverflowMode::Saturating);
+    interpreter.eval(&ast)?;
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(i64::MAX)));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(i64::MIN)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(i64::MAX)));
+    assert_eq!(interpreter.variables.get("d"), Some(&Value::Int(i64::MAX)));
+
+    //Division by zero is an error in every mode
+    let result = interpreter.eval(&parse_program("x = 1 / 0;")?);
+    assert!(matches!(result, Err(EvalError::DivisionByZero { .. })));
+
+    Ok(())
+}
+
+///Test grammar rule: float
+#[test]
+fn test_float_rule() -> Result<()> {
+    for (code, expected) in [
+        ("2.75;", 2.75),
+        (".5;", 0.5),
+        ("1e-3;", 0.001),
+        ("2.5E+2;", 250.0),
+    ] {
+        let result = parse_program(code)?;
+        assert_eq!(result[0].node, ASTNode::Float(expected), "{}", code);
+    }
+
+    //Integers without a fraction or exponent stay ints
+    assert_eq!(parse_program("42;")?[0].node, ASTNode::Number(42));
+
+    assert!(parse_program("1.;").is_err());
+    assert!(parse_program("1e;").is_err());
+    assert!(matches!(
+        parse_program("x = 1e999;"),
+        Err(ParseError::InvalidNumber { .. })
+    ));
+
+    Ok(())
+}
+
+///Test promotion between ints and floats and float division by zero
+#[test]
+fn test_float_arithmetic() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    let code = "a = 7 / 2; b = 7 / 2.0; c = 1 + .5; d = 2 * 1.5 == 3; e = 0.1 < 1;";
+    interpreter.eval(&parse_program(code)?)?;
+
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(3)));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Float(3.5)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Float(1.5)));
+    assert_eq!(interpreter.variables.get("d"), Some(&Value::Bool(true)));
+    assert_eq!(interpreter.variables.get("e"), Some(&Value::Bool(true)));
+    assert_eq!(Value::Float(3.0).to_string(), "3.0");
+
+    let result = interpreter.eval(&parse_program("x = 1.5 + true;")?);
+    assert!(matches!(
+        result,
+        Err(EvalError::TypeMismatch {
+            left: "float",
+            right: "bool",
+            ..
+        })
+    ));
+
+    //Division by zero is an error by default
+    let result = interpreter.eval(&parse_program("x = 1.0 / 0;")?);
+    assert!(matches!(result, Err(EvalError::DivisionByZero { .. })));
+
+    interpreter.set_float_division_mode(FloatDivisionMode::Infinity);
+    interpreter.eval(&parse_program(
+        "x = 1.0 / 0; y = 0 - 1 / 0.0; z = 0.0 / 0;",
+    )?)?;
+    assert_eq!(
+        interpreter.variables.get("x"),
+        Some(&Value::Float(f64::INFINITY))
+    );
+    assert_eq!(
+        interpreter.variables.get("y"),
+        Some(&Value::Float(f64::NEG_INFINITY))
+    );
+    assert!(matches!(interpreter.variables.get("z"), Some(Value::Float(z)) if z.is_nan()));
+
+    //Integer division by zero is still an error
+    let result = interpreter.eval(&parse_program("x = 1 / 0;")?);
+    assert!(matches!(result, Err(EvalError::DivisionByZero { .. })));
+
+    Ok(())
+}
+
+///Test exact rational division and arithmetic
+#[test]
+fn test_exact_division() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_integer_division_mode(IntegerDivisionMode::Exact);
+    let code = r#"
+        a = 7 / 2;
+        b = 6 / 3;
+        c = a + 1 / 2;
+        d = 1 / 3 - 1 / 2;
+        e = a * 2 / 7;
+        f = 1 / 3 < 1 / 2;
+        g = 2 / 4 == 1 / 2;
+        h = a / 2.0;
+    "#;
+    interpreter.eval(&parse_program(code)?)?;
+
+    let rational = |n, d| Rational::new(n, d).map(Value::Rational);
+    assert_eq!(interpreter.variables.get("a").cloned(), rational(7, 2));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(2)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(4)));
+    assert_eq!(interpreter.variables.get("d").cloned(), rational(-1, 6));
+    assert_eq!(interpreter.variables.get("e"), Some(&Value::Int(1)));
+    assert_eq!(interpreter.variables.get("f"), Some(&Value::Bool(true)));
+    assert_eq!(interpreter.variables.get("g"), Some(&Value::Bool(true)));
+    assert_eq!(interpreter.variables.get("h"), Some(&Value::Float(1.75)));
+
+    //Printed as fractions, or as decimals on request
+    let d = &interpreter.variables["d"];
+    assert_eq!(d.to_string(), "-1/6");
+    assert_eq!(d.to_decimal_string(3), "-0.167");
+    assert_eq!(interpreter.variables["a"].to_decimal_string(2), "3.50");
+    assert_eq!(Value::Int(5).to_decimal_string(1), "5.0");
+
+    let result = interpreter.eval(&parse_program("x = a / (a - a);")?);
+    assert!(matches!(result, Err(EvalError::DivisionByZero { .. })));
+    let result = interpreter.eval(&parse_program("x = 1 / 9223372036854775807 / 2;")?);
+    assert!(matches!(result, Err(EvalError::Overflow { op: "/", .. })));
+
+    //Truncating division is the default
+    interpreter.set_integer_division_mode(IntegerDivisionMode::Truncate);
+    interpreter.eval(&parse_program("x = 7 / 2; y = a * 2;")?)?;
+    assert_eq!(interpreter.variables.get("x"), Some(&Value::Int(3)));
+    assert_eq!(interpreter.variables.get("y"), Some(&Value::Int(7)));
+
+    Ok(())
+}
+
+///Test evaluation of remainder, exponentiation and unary operators
+#[test]
+fn test_arithmetic_operators() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    let code = r#"
+        a = -2 ** 2;
+        b = 2 ** 3 ** 2;
+        c = -7 % 3;
+        d = 7 % -3;
+        e = +4 - -1;
+        f = 2.0 ** -1;
+        g = 7.5 % 2;
+        h = 1 ** 10000000000;
+        x = 5;
+        i = -x;
+    "#;
+    interpreter.eval(&parse_program(code)?)?;
+
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Int(-4)));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(512)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(-1)));
+    assert_eq!(interpreter.variables.get("d"), Some(&Value::Int(1)));
+    assert_eq!(interpreter.variables.get("e"), Some(&Value::Int(5)));
+    assert_eq!(interpreter.variables.get("f"), Some(&Value::Float(0.5)));
+    assert_eq!(interpreter.variables.get("g"), Some(&Value::Float(1.5)));
+    assert_eq!(interpreter.variables.get("h"), Some(&Value::Int(1)));
+    assert_eq!(interpreter.variables.get("i"), Some(&Value::Int(-5)));
+
+    let result = interpreter.eval(&parse_program("y = 5 % 0;")?);
+    assert!(matches!(result, Err(EvalError::ModuloByZero { .. })));
+    let result = interpreter.eval(&parse_program("y = 5 % 0.0;")?);
+    assert!(matches!(result, Err(EvalError::ModuloByZero { .. })));
+    let result = interpreter.eval(&parse_program("y = 2 ** -1;")?);
+    assert!(matches!(result, Err(EvalError::NegativeExponent { .. })));
+    let result = interpreter.eval(&parse_program("y = -true;")?);
+    assert!(matches!(
+        result,
+        Err(EvalError::TypeError {
+            expected: "number",
+            found: "bool",
+            ..
+        })
+    ));
+
+    //Negating i64::MIN and large powers follow the overflow mode
+    interpreter.set_overflow_mode(OverflowMode::Checked);
+    let min = "small = -9223372036854775807 - 1;";
+    interpreter.eval(&parse_program(min)?)?;
+    for (code, op) in [("y = -small;", "-"), ("y = 3 ** 40;", "**")] {
+        let result = interpreter.eval(&parse_program(code)?);
+        assert!(
+            matches!(result, Err(EvalError::Overflow { op: found, .. }) if found == op),
+            "{} should overflow",
+            code
+        );
+    }
+    interpreter.set_overflow_mode(OverflowMode::Saturating);
+    interpreter.eval(&parse_program("y = -small; z = (-3) ** 41;")?)?;
+    assert_eq!(interpreter.variables.get("y"), Some(&Value::Int(i64::MAX)));
+    assert_eq!(interpreter.variables.get("z"), Some(&Value::Int(i64::MIN)));
+    interpreter.set_overflow_mode(OverflowMode::Wrapping);
+    interpreter.eval(&parse_program("y = -small; z = 2 ** 64;")?)?;
+    assert_eq!(interpreter.variables.get("y"), Some(&Value::Int(i64::MIN)));
+    assert_eq!(interpreter.variables.get("z"), Some(&Value::Int(0)));
+
+    //Exact division turns negative powers into rationals
+    interpreter.set_integer_division_mode(IntegerDivisionMode::Exact);
+    interpreter.eval(&parse_program(
+        "y = 2 ** -2; z = (2 / 3) ** 2; w = -(1 / 2) % (1 / 3);",
+    )?)?;
+    let rational = |n, d| Rational::new(n, d).map(Value::Rational);
+    assert_eq!(interpreter.variables.get("y").cloned(), rational(1, 4));
+    assert_eq!(interpreter.variables.get("z").cloned(), rational(4, 9));
+    assert_eq!(interpreter.variables.get("w").cloned(), rational(-1, 6));
+
+    //Fractional powers are not rounded to floats
+    let result = interpreter.eval(&parse_program("v = 4 ** (1 / 2);")?);
+    assert!(
+        matches!(&result, Err(EvalError::InexactPower { exponent, .. }) if Some(exponent.clone()) == rational(1, 2)),
+        "{:?}",
+        result
+    );
+    interpreter.eval(&parse_program("v = 4.0 ** (1 / 2);")?)?;
+    assert_eq!(interpreter.variables.get("v"), Some(&Value::Float(2.0)));
+
+    Ok(())
+}
+
+///Test evaluation of bitwise and shift operators
+#[test]
+fn test_bitwise_operators() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    let code = r#"
+        flags = 5;
+        a = (flags & 4) != 0;
+        b = flags | 1 << 3;
+        c = ~0 ^ 7;
+        d = -16 >> 2;
+        e = -1 << 63;
+        f = true ^ false;
+        g = true & false;
+    "#;
+    interpreter.eval(&parse_program(code)?)?;
+
+    assert_eq!(interpreter.variables.get("a"), Some(&Value::Bool(true)));
+    assert_eq!(interpreter.variables.get("b"), Some(&Value::Int(13)));
+    assert_eq!(interpreter.variables.get("c"), Some(&Value::Int(-8)));
+    assert_eq!(interpreter.variables.get("d"), Some(&Value::Int(-4)));
+    assert_eq!(interpreter.variables.get("e"), Some(&Value::Int(i64::MIN)));
+    assert_eq!(interpreter.variables.get("f"), Some(&Value::Bool(true)));
+    assert_eq!(interpreter.variables.get("g"), Some(&Value::Bool(false)));
+
+    //Shifting bits other than copies of the sign bit out follows the overflow mode
+    interpreter.set_overflow_mode(OverflowMode::Checked);
+    let result = interpreter.eval(&parse_program("x = 1 << 63;")?);
+    assert!(matches!(result, Err(EvalError::Overflow { op: "<<", .. })));
+    for (mode, x, y) in [
+        (OverflowMode::Wrapping, i64::MIN, 0),
+        (OverflowMode::Saturating, i64::MAX, i64::MIN),
+    ] {
+        interpreter.set_overflow_mode(mode);
+        interpreter.eval(&parse_program("x = 1 << 63; y = -4 << 63;")?)?;
+        assert_eq!(interpreter.variables.get("x"), Some(&Value::Int(x)));
+        assert_eq!(interpreter.variables.get("y"), Some(&Value::Int(y)));
+    }
+
+    for (code, amount) in [("x = 1 << 64;", 64), ("x = 1 >> -1;", -1)] {
+        let result = interpreter.eval(&parse_program(code)?);
+        assert!(
+            matches!(&result, Err(EvalError::InvalidShift { amount: found, .. }) if *found == Value::Int(amount)),
+            "{} should fail with an invalid shift",
+            code
+        );
+    }
+
+    //Comparisons bind tighter than &, as in C
+    let result = interpreter.eval(&parse_program("x = flags & 4 == 4;")?);
+    assert!(matches!(
+        result,
+        Err(EvalError::TypeMismatch {
+            op: "&",
+            left: "int",
+            right: "bool",
+            ..
+        })
+    ));
+    let result = interpreter.eval(&parse_program("x = ~1.5;")?);
+    assert!(matches!(
+        result,
+        Err(EvalError::TypeError {
+            expected: "int",
+            found: "float",
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+///Test grammar rule: number with prefixes and digit separators
+#[test]
+fn test_number_literals() -> Result<()> {
+    for (code, expected) in [
+        ("0xFF;", 255),
+        ("0xdead_BEEF;", 0xdead_beef),
+        ("0b1010;", 10),
+        ("0o17;", 15),
+        ("1_000_000;", 1_000_000),
+        ("0x7fff_ffff_ffff_ffff;", i64::MAX),
+        ("007;", 7),
+    ] {
+        let result = parse_program(code)?;
+        assert_eq!(result[0].node, ASTNode::Number(expected), "{}", code);
+    }
+    assert_eq!(parse_program("1_000.5;")?[0].node, ASTNode::Float(1000.5));
+
+    #[cfg(not(feature = "bigint"))]
+    let overflowing: &[_] = &["0x8000000000000000", "9223372036854775808"];
+    #[cfg(feature = "bigint")]
+    let overflowing: &[_] = &[];
+    for (literal, reason) in [
+        ("0b102", "`2` is not a binary digit"),
+        ("0o8", "`8` is not an octal digit"),
+        ("0xfg", "`g` is not a hexadecimal digit"),
+        ("0x_", "no digits after the hexadecimal prefix"),
+    ]
+    .into_iter()
+    .chain(
+        overflowing
+            .iter()
+            .map(|literal| (*literal, "does not fit in a signed 64-bit integer")),
+    ) {
+        let code = format!("x = 1;\ny = {};", literal);
+        match parse_program(&code) {
+            Err(ParseError::InvalidNumber {
+                literal: found,
+                reason: message,
+                span,
+            }) => {
+                assert_eq!(found, literal);
+                assert!(message.starts_with(reason), "{}: {}", literal, message);
+                assert_eq!((span.line, span.column), (2, 5));
+                assert_eq!(&code[span.start..span.end], literal);
+            }
+            other => panic!("Expected InvalidNumber for {}, got {:?}", literal, other),
+        }
+    }
+
+    //Prefixes are lowercase, like in Rust
+    assert!(parse_program("0XFF;").is_err());
+
+    Ok(())
+}
+
+///Test that line and nested block comments are skipped like whitespace
+#[test]
+fn test_comments() -> Result<()> {
+    let code =
+        "// leading\nx = 1 /* inline /* nested */ still */ + 2; // trailing\n/* } */ y = x //\n;";
+    let result = parse_program(code)?;
+    assert_eq!(result.len(), 2);
+
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&result)?;
+    assert_eq!(interpreter.variables.get("x"), Some(&Value::Int(3)));
+    assert_eq!(interpreter.variables.get("y"), Some(&Value::Int(3)));
+
+    //A comment ends the line it is on, and does not split tokens
+    assert!(parse_program("x = 1; // y = ;").is_ok());
+    assert!(parse_program("x = 1 /* */ 2;").is_err());
+    assert!(parse_program("x = 1; /* /* */").is_err());
+    assert!(parse_program("x = 1; */").is_err());
+
+    Ok(())
+}
+
+///Test that doc comments are attached to the assignment that follows them
+#[test]
+fn test_doc_comment_rule() -> Result<()> {
+    let code = "/// The answer\n///\n///   indented\nx = 42;\n//// not a doc\ny = 1;\nfn f() {\n    /// local\n    z = 2;\n    return z;\n}";
+    let result = parse_program(code)?;
+
+    if let ASTNode::Assignment { name, doc, .. } = &result[0].node {
+        assert_eq!(name, "x");
+        assert_eq!(doc.as_deref(), Some("The answer\n\n  indented"));
+        //The span still covers only the assignment
+        assert_eq!(&code[result[0].span.start..result[0].span.end], "x = 42");
+    } else {
+        panic!("Expected Assignment");
+    }
+    assert!(matches!(
+        &result[1].node,
+        ASTNode::Assignment { doc: None, .. }
+    ));
+
+    if let ASTNode::FunctionDef { body, .. } = &result[2].node {
+        assert!(
+            matches!(&body[0].node, ASTNode::Assignment { doc: Some(doc), .. } if doc == "local")
+        );
+    } else {
+        panic!("Expected FunctionDef");
+    }
+
+    //Doc comments that document no statement are ordinary comments
+    for code in [
+        "x = 1;\n/// trailing",
+        "if (true) {\n    x = 1;\n    /// before the brace\n}",
+        "x = 1 +\n    /// inside an expression\n    2;",
+    ] {
+        let result = parse_program(code)?;
+        assert_eq!(result.len(), 1, "{}", code);
+        assert!(
+            !matches!(&result[0].node, ASTNode::Assignment { doc: Some(_), .. }),
+            "{}",
+            code
+        );
+    }
+    //Only lines right above a statement that begins its own line count
+    let result = parse_program("/// about x\nx = 1; y = 2;")?;
+    assert!(
+        matches!(&result[0].node, ASTNode::Assignment { doc: Some(doc), .. } if doc == "about x")
+    );
+    assert!(matches!(
+        &result[1].node,
+        ASTNode::Assignment { doc: None, .. }
+    ));
+
+    //Blank lines and other comments end a doc comment, and /// inside a
+    //block comment is part of the block comment
+    for (code, expected) in [
+        ("/// about x\n\nx = 1;", None),
+        ("/// old\n\n/// about x\nx = 1;", Some("about x")),
+        ("/// old\n// note\nx = 1;", None),
+        ("/*\n/// x */\nx = 1;", None),
+        ("/* /// x */ /// about x\nx = 1;", Some("about x")),
+        ("s = \"/*\";\n/// about x\nx = 1;", Some("about x")),
+    ] {
+        let result = parse_program(code)?;
+        let doc = match &result[result.len() - 1].node {
+            ASTNode::Assignment { doc, .. } => doc.as_deref(),
+            _ => panic!("Expected Assignment"),
+        };
+        assert_eq!(doc, expected, "{}", code);
+    }
+
+    Ok(())
+}
+
+///Test grammar rule: identifier
+#[test]
+fn test_identifier_rule() -> Result<()> {
+    let code = "maxValue = 1; x1 = 2; total_cost = maxValue + x1; _ = 3; größe = 4; 変数 = größe;\nbreakfast = 5; iffy = 6; return_value = 7; trueish = 8;";
+    let result = parse_program(code)?;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&result)?;
+    for (name, value) in [
+        ("total_cost", 3),
+        ("_", 3),
+        ("変数", 4),
+        ("breakfast", 5),
+        ("iffy", 6),
+        ("return_value", 7),
+        ("trueish", 8),
+    ] {
+        assert_eq!(interpreter.variables.get(name), Some(&Value::Int(value)));
+    }
+
+    //Identifiers cannot start with a digit
+    assert!(parse_program("1x = 2;").is_err());
+
+    //A precomposed é and e followed by a combining acute accent
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program("caf\u{e9} = 1; cafe\u{301} = 2;")?)?;
+    #[cfg(feature = "nfc")]
+    assert_eq!(interpreter.variables.len(), 1);
+    #[cfg(not(feature = "nfc"))]
+    assert_eq!(interpreter.variables.len(), 2);
+
+    Ok(())
+}
+
+///Test that keywords cannot be used as names
+#[test]
+fn test_reserved_keywords() {
+    for code in [
+        "if = 1;",
+        "return = 2;",
+        "x = while + 1;",
+        "true = 1;",
+        "fn f(return) { }",
+        "fn else() { }",
+        "x = break(1);",
+        "for = 1;",
+        "fn f(in) { }",
+        "for in in xs { }",
+    ] {
+        match parse_program(code) {
+            Err(ParseError::ReservedKeyword { keyword, span }) => {
+                assert!(KEYWORDS.contains(&keyword.as_str()));
+                assert_eq!(&code[span.start..span.end], keyword);
+            }
+            other => panic!("Expected ReservedKeyword for {:?}, got {:?}", code, other),
+        }
+    }
+}
+
+///Test grammar rule: print_statement
+#[test]
+fn test_print_rule() -> Result<()> {
+    let result = parse_program("print 1, x + 2; print; printer = 3;")?;
+
+    if let ASTNode::Print(values) = &result[0].node {
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].node, ASTNode::Number(1));
+        assert!(matches!(values[1].node, ASTNode::Add(_, _)));
+    } else {
+        panic!("Expected Print");
+    }
+    assert_eq!(result[1].node, ASTNode::Print(vec![]));
+    assert!(matches!(&result[2].node, ASTNode::Assignment { name, .. } if name == "printer"));
+
+    assert!(parse_program("print 1,;").is_err());
+    assert!(matches!(
+        parse_program("print = 1;"),
+        Err(ParseError::ReservedKeyword { .. })
+    ));
+
+    Ok(())
+}
+
+///Test that print output goes to the configured sink
+#[test]
+fn test_print_output() -> Result<()> {
+    let ast = parse_program("x = 7 / 2; print x, x * 2.0, x > 3; print;")?;
+
+    let output = BufferSink::new();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(output.clone());
+    interpreter.eval(&ast)?;
+    assert_eq!(output.lines(), ["3 6.0 false", ""]);
+    assert_eq!(output.take().len(), 2);
+    assert!(output.lines().is_empty());
+
+    //Any closure can receive the lines
+    let (sender, receiver) = std::sync::mpsc::channel();
+    interpreter.set_output(move |line: &str| sender.send(line.to_string()).unwrap());
+    interpreter.eval(&ast)?;
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), ["3 6.0 false", ""]);
+
+    Ok(())
+}
+
+///Test grammar rule: string
+#[test]
+fn test_string_rule() -> Result<()> {
+    let code = r#"s = "tab\there \"quoted\" back\\slash \u{48}\u{e9}\u{1F600}\n// not a comment";"#;
+    let result = parse_program(code)?;
+    if let ASTNode::Assignment { value, .. } = &result[0].node {
+        assert_eq!(
+            value.node,
+            ASTNode::String(
+                "tab\there \"quoted\" back\\slash H\u{e9}\u{1F600}\n// not a comment".to_string()
+            )
+        );
+    } else {
+        panic!("Expected Assignment");
+    }
+    assert_eq!(
+        parse_program(r#""";"#)?[0].node,
+        ASTNode::String(String::new())
+    );
+
+    for (code, escape, reason) in [
+        (r#"x = 1;\ny = "ok \q";"#, r"\q", "unknown escape sequence"),
+        (
+            r#"x = 1;\ny = "ok \u41";"#,
+            r"\u",
+            "expected `{` after `\\u`",
+        ),
+        (r#"x = 1;\ny = "ok \u{}";"#, r"\u{}", "expected one to six"),
+        (
+            r#"x = 1;\ny = "ok \u{d800}";"#,
+            r"\u{d800}",
+            "not a Unicode scalar value",
+        ),
+    ] {
+        let code = code.replace("\\n", "\n");
+        match parse_program(&code) {
+            Err(ParseError::InvalidEscape {
+                escape: found,
+                reason: message,
+                span,
+            }) => {
+                assert_eq!(found, escape);
+                assert!(message.starts_with(reason), "{}: {}", escape, message);
+                assert_eq!((span.line, span.column), (2, 9));
+                assert_eq!(&code[span.start..span.end], escape);
+            }
+            other => panic!("Expected InvalidEscape for {}, got {:?}", escape, other),
+        }
+    }
+    assert!(parse_program(r#"s = "unterminated;"#).is_err());
+
+    Ok(())
+}
+
+///Test grammar rule: string interpolation
+#[test]
+fn test_interpolation_rule() -> Result<()> {
+    let result = parse_program(r#"s = "total = {a + b}!";"#)?;
+    if let ASTNode::Assignment { value, .. } = &result[0].node {
+        if let ASTNode::Interpolation(parts) = &value.node {
+            assert_eq!(parts.len(), 3);
+            assert_eq!(parts[0].node, ASTNode::String("total = ".to_string()));
+            assert!(matches!(parts[1].node, ASTNode::Add(_, _)));
+            //Embedded expressions keep spans inside the literal
+            assert_eq!((parts[1].span.line, parts[1].span.column), (1, 15));
+            assert_eq!(parts[2].node, ASTNode::String("!".to_string()));
+        } else {
+            panic!("Expected Interpolation");
+        }
+    } else {
+        panic!("Expected Assignment");
+    }
+
+    //Escaped braces stay literal text
+    assert_eq!(
+        parse_program(r#""\{a\}";"#)?[0].node,
+        ASTNode::String("{a}".to_string())
+    );
+    //Strings nest inside interpolations
+    if let ASTNode::Interpolation(parts) = &parse_program(r#""{"}"}";"#)?[0].node {
+        assert_eq!(parts[0].node, ASTNode::String("}".to_string()));
+    } else {
+        panic!("Expected Interpolation");
+    }
+
+    //Errors inside an interpolation point into the string
+    for code in [r#"x = 1;\ns = "a {1 +} b";"#, r#"x = 1;\ns = "a {} b";"#] {
+        let code = code.replace("\\n", "\n");
+        match parse_program(&code) {
+            Err(ParseError::PestError(e)) => match e.line_col {
+                pest::error::LineColLocation::Pos(position) => {
+                    assert_eq!(position.0, 2, "{}", code);
+                    assert!(position.1 > 5, "{}", code);
+                }
+                other => panic!("Expected a position, got {:?}", other),
+            },
+            other => panic!("Expected grammar error for {}, got {:?}", code, other),
+        }
+    }
+
+    Ok(())
+}
+
+///Test evaluation of string interpolation
+#[test]
+fn test_interpolation_evaluation() -> Result<()> {
+    let code = r#"
+        a = 2;
+        b = 3;
+        name = "wörld";
+        total = "total = {a + b}";
+        mixed = "{name[0]}{len(name)} {a < b} {1.5} {"\{"}";
+        "#;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program(code)?)?;
+
+    assert_eq!(
+        interpreter.variables.get("total"),
+        Some(&Value::from("total = 5"))
+    );
+    assert_eq!(
+        interpreter.variables.get("mixed"),
+        Some(&Value::from("w5 true 1.5 {"))
+    );
+
+    let error = Interpreter::new()
+        .eval(&parse_program(r#"s = "{missing}";"#)?)
+        .unwrap_err();
+    assert!(matches!(error, EvalError::UndefinedVariable { .. }));
+
+    Ok(())
+}
+
+///Test grammar rules: array and index assignment
+#[test]
+fn test_array_rule() -> Result<()> {
+    let result = parse_program("[1, x + 1, []]; xs[0] = 5; grid[i][-1] = [];")?;
+
+    if let ASTNode::Array(elements) = &result[0].node {
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[0].node, ASTNode::Number(1));
+        assert!(matches!(elements[1].node, ASTNode::Add(_, _)));
+        assert_eq!(elements[2].node, ASTNode::Array(vec![]));
+    } else {
+        panic!("Expected Array");
+    }
+
+    if let ASTNode::IndexAssignment {
+        name,
+        indexes,
+        value,
+    } = &result[1].node
+    {
+        assert_eq!(name, "xs");
+        assert_eq!(indexes.len(), 1);
+        assert_eq!(indexes[0].node, ASTNode::Number(0));
+        assert_eq!(value.node, ASTNode::Number(5));
+    } else {
+        panic!("Expected IndexAssignment");
+    }
+
+    if let ASTNode::IndexAssignment { name, indexes, .. } = &result[2].node {
+        assert_eq!(name, "grid");
+        assert_eq!(indexes[0].node, ASTNode::Identifier("i".to_string()));
+        assert!(matches!(indexes[1].node, ASTNode::Neg(_)));
+    } else {
+        panic!("Expected IndexAssignment");
+    }
+
+    //Comparing an element is still an expression
+    assert!(matches!(
+        parse_program("xs[0] == 5;")?[0].node,
+        ASTNode::Eq(_, _)
+    ));
+    assert!(parse_program("xs[1:2] = 5;").is_err());
+    assert!(parse_program("f()[0] = 5;").is_err());
+    assert!(parse_program("[1, 2,];").is_err());
+
+    Ok(())
+}
+
+///Test evaluation of arrays
+#[test]
+fn test_array_operations() -> Result<()> {
+    let code = r#"
+        xs = [10, 20, 30];
+        first = xs[0];
+        last = xs[-1];
+        n = len(xs);
+        xs[1] = "twenty";
+        xs[-1] = xs[-1] + 1;
+        grid = [[0, 0], [0, 0]];
+        grid[1][0] = 5;
+        copy = grid;
+        copy[0][1] = 7;
+        tail = xs[-2:];
+        empty = len([]);
+        fn fill(v) { i = 0; while (i < len(ys)) { ys[i] = v; i = i + 1; } }
+        ys = [1, 2, 3];
+        fill(0);
+        text = "{xs} {[1.5, true, "a, b"]}";
+        chars = "héllo"[-4];
+    "#;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program(code)?)?;
+
+    let get = |name: &str| interpreter.variables.get(name).cloned();
+    assert_eq!(get("first"), Some(Value::Int(10)));
+    assert_eq!(get("last"), Some(Value::Int(30)));
+    assert_eq!(get("n"), Some(Value::Int(3)));
+    assert_eq!(
+        get("xs"),
+        Some(Value::from(vec![
+            Value::Int(10),
+            Value::from("twenty"),
+            Value::Int(31)
+        ]))
+    );
+    assert_eq!(
+        get("tail"),
+        Some(Value::from(vec![Value::from("twenty"), Value::Int(31)]))
+    );
+    assert_eq!(get("empty"), Some(Value::Int(0)));
+    //Assigning an array copies it
+    assert_eq!(
+        get("grid").map(|v| v.to_string()),
+        Some("[[0, 0], [5, 0]]".to_string())
+    );
+    assert_eq!(
+        get("copy").map(|v| v.to_string()),
+        Some("[[0, 7], [5, 0]]".to_string())
+    );
+    //Functions change global arrays they do not assign locally
+    assert_eq!(get("ys"), Some(Value::from(vec![Value::Int(0); 3])));
+    assert_eq!(
+        get("text"),
+        Some(Value::from(r#"[10, "twenty", 31] [1.5, true, "a, b"]"#))
+    );
+    assert_eq!(get("chars"), Some(Value::from("é")));
+
+    for (code, check) in [
+        ("xs = [1, 2]; x = xs[2];", "index"),
+        ("xs = [1, 2]; x = xs[-3];", "index"),
+        ("xs = [1, 2]; xs[5] = 0;", "index"),
+        ("xs = [[1], 2]; xs[1][0] = 0;", "type"),
+        (r#"s = "ab"; s[0] = "c";"#, "type"),
+        ("xs = [1]; x = xs[true];", "type"),
+        ("x = 1; x[0] = 2;", "type"),
+        ("missing[0] = 1;", "undefined"),
+    ] {
+        let error = Interpreter::new().eval(&parse_program(code)?).unwrap_err();
+        let matched = match check {
+            "index" => matches!(error, EvalError::IndexOutOfBounds { len: 2, .. }),
+            "type" => matches!(error, EvalError::TypeError { .. }),
+            _ => matches!(error, EvalError::UndefinedVariable { .. }),
+        };
+        assert!(matched, "{}: {:?}", code, error);
+    }
+
+    Ok(())
+}
+
+///Test grammar rules: map, field and field assignment
+#[test]
+fn test_map_rule() -> Result<()> {
+    let result = parse_program(r#"{ name: 1, "two words": x }; {}; obj.size; obj.a["b"].c = 2;"#)?;
+
+    if let ASTNode::Map(entries) = &result[0].node {
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0.node, ASTNode::String("name".to_string()));
+        assert_eq!(entries[0].1.node, ASTNode::Number(1));
+        assert_eq!(entries[1].0.node, ASTNode::String("two words".to_string()));
+        assert_eq!(entries[1].1.node, ASTNode::Identifier("x".to_string()));
+    } else {
+        panic!("Expected Map");
+    }
+    assert_eq!(result[1].node, ASTNode::Map(vec![]));
+
+    //A field is an index by its name
+    if let ASTNode::Index { target, index } = &result[2].node {
+        assert_eq!(target.node, ASTNode::Identifier("obj".to_string()));
+        assert_eq!(index.node, ASTNode::String("size".to_string()));
+        assert_eq!((index.span.line, index.span.column), (1, 38));
+    } else {
+        panic!("Expected Index");
+    }
+
+    if let ASTNode::IndexAssignment { name, indexes, .. } = &result[3].node {
+        assert_eq!(name, "obj");
+        let keys: Vec<_> = indexes.iter().map(|index| &index.node).collect();
+        assert_eq!(
+            keys,
+            [
+                &ASTNode::String("a".to_string()),
+                &ASTNode::String("b".to_string()),
+                &ASTNode::String("c".to_string()),
+            ]
+        );
+    } else {
+        panic!("Expected IndexAssignment");
+    }
+
+    //Blocks are not maps
+    assert!(matches!(
+        parse_program("if (true) { x = 1; }")?[0].node,
+        ASTNode::If { .. }
+    ));
+    for code in [
+        "m = { a };",
+        "m = { 1: 2 };",
+        "m = { a: 1, };",
+        "x = m.;",
+        "x = m.if;",
+    ] {
+        assert!(parse_program(code).is_err(), "{}", code);
+    }
+
+    Ok(())
+}
+
+///Test evaluation of maps
+#[test]
+fn test_map_operations() -> Result<()> {
+    let code = r#"
+        fn stats(xs) {
+            total = 0;
+            i = 0;
+            while (i < len(xs)) {
+                total = total + xs[i];
+                i = i + 1;
+            }
+            return { count: len(xs), total: total };
+        }
+        result = stats([1, 2, 3]);
+        count = result.count;
+        total = result["total"];
+        result.mean = result.total / result.count;
+        result["count"] = 0;
+        nested = { inner: { values: [1, 2] } };
+        nested.inner.values[-1] = 5;
+        names = keys({ b: 1, a: 2, "c d": 3 });
+        sum = 0;
+        i = 0;
+        while (i < len(names)) {
+            sum = sum + len(names[i]);
+            i = i + 1;
+        }
+        size = len(result);
+        same = { a: 1, a: 2 }.a;
+        text = "{nested}";
+    "#;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&parse_program(code)?)?;
+
+    let get = |name: &str| interpreter.variables.get(name).cloned();
+    assert_eq!(get("count"), Some(Value::Int(3)));
+    assert_eq!(get("total"), Some(Value::Int(6)));
+    assert_eq!(
+        get("result").map(|v| v.to_string()),
+        Some(r#"{"count": 0, "mean": 2, "total": 6}"#.to_string())
+    );
+    assert_eq!(get("size"), Some(Value::Int(3)));
+    assert_eq!(
+        get("names"),
+        Some(Value::from(vec![
+            Value::from("a"),
+            Value::from("b"),
+            Value::from("c d")
+        ]))
+    );
+    assert_eq!(get("sum"), Some(Value::Int(5)));
+    assert_eq!(get("same"), Some(Value::Int(2)));
+    assert_eq!(
+        get("text"),
+        Some(Value::from(r#"{"inner": {"values": [1, 5]}}"#))
+    );
+
+    for (code, check) in [
+        ("m = { a: 1 }; x = m.b;", "key"),
+        (r#"m = { a: 1 }; x = m["A"];"#, "key"),
+        ("m = { a: {} }; m.a.b.c = 1;", "key"),
+        ("m = { a: 1 }; x = m[0];", "type"),
+        ("m = { a: 1 }; x = m[0:];", "type"),
+        ("x = 1; y = x.a;", "type"),
+        (r#"s = "ab"; s.a = 1;"#, "type"),
+        ("x = keys([1]);", "host"),
+    ] {
+        let error = Interpreter::new().eval(&parse_program(code)?).unwrap_err();
+        let matched = match check {
+            "key" => matches!(error, EvalError::MissingKey { .. }),
+            "type" => matches!(error, EvalError::TypeError { .. }),
+            _ => matches!(error, EvalError::HostError { .. }),
+        };
+        assert!(matched, "{}: {:?}", code, error);
+    }
+
+    Ok(())
+}
+
+///Test grammar rules: postfix, index and slice
+#[test]
+fn test_index_rule() -> Result<()> {
+    let result = parse_program("s[1]; s[1:][0]; s[:2]; s[a:b]; s[:];")?;
+
+    if let ASTNode::Index { target, index } = &result[0].node {
+        assert_eq!(target.node, ASTNode::Identifier("s".to_string()));
+        assert_eq!(index.node, ASTNode::Number(1));
+    } else {
+        panic!("Expected Index");
+    }
+    //Indexes apply left to right
+    if let ASTNode::Index { target, .. } = &result[1].node {
+        assert!(matches!(
+            &target.node,
+            ASTNode::Slice {
+                start: Some(_),
+                end: None,
+                ..
+            }
+        ));
+    } else {
+        panic!("Expected Index");
+    }
+    assert!(matches!(
+        &result[2].node,
+        ASTNode::Slice {
+            start: None,
+            end: Some(_),
+            ..
+        }
+    ));
+    assert!(matches!(
+        &result[3].node,
+        ASTNode::Slice {
+            start: Some(_),
+            end: Some(_),
+            ..
+        }
+    ));
+    assert!(matches!(
+        &result[4].node,
+        ASTNode::Slice {
+            start: None,
+            end: None,
+            ..
+        }
+    ));
+
+    //Indexing binds tighter than prefix operators and `**`
+    let result = parse_program("-s[0] ** 2;")?;
+    if let ASTNode::Neg(operand) = &result[0].node {
+        if let ASTNode::Pow(base, _) = &operand.node {
+            assert!(matches!(base.node, ASTNode::Index { .. }));
+        } else {
+            panic!("Expected Pow");
+        }
+    } else {
+        panic!("Expected Neg");
+    }
+
+    Ok(())
+}
+
+///Test string concatenation, comparison, len, indexing and slicing
+#[test]
+fn test_string_operations() -> Result<()> {
+    let code = r#"
+        name = "wörld";
+        greeting = "hello, " + name + "!";
+        n = len(greeting);
+        first = greeting[0];
+        last = greeting[n - 1];
+        word = greeting[7:12];
+        head = greeting[:5];
+        tail = name[3:];
+        all = name[:];
+        empty = name[5:];
+        same = "abc" == "abc";
+        differ = "abc" != "abd";
+        before = "apple" < "banana";
+        prefix = "ab" <= "abc";
+    "#;
+    let ast = parse_program(code)?;
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&ast)?;
+
+    for (name, value) in [
+        ("greeting", "hello, wörld!"),
+        ("first", "h"),
+        ("last", "!"),
+        ("word", "wörld"),
+        ("head", "hello"),
+        ("tail", "ld"),
+        ("all", "wörld"),
+        ("empty", ""),
+    ] {
+        assert_eq!(interpreter.variables.get(name), Some(&Value::from(value)));
+    }
+    //Lengths count characters, not bytes
+    assert_eq!(interpreter.variables.get("n"), Some(&Value::Int(13)));
+    for name in ["same", "differ", "before", "prefix"] {
+        assert_eq!(interpreter.variables.get(name), Some(&Value::Bool(true)));
+    }
+
+    for (code, check) in [
+        (r#"x = "a" * "b";"#, "mismatched" as &str),
+        (r#"x = "a" + 1;"#, "mismatched"),
+        (r#"x = "a" == 1;"#, "mismatched"),
+        (r#"x = -"a";"#, "type"),
+        (r#"x = "abc"[3];"#, "index"),
+        (r#"x = "abc"[-4];"#, "index"),
+        (r#"x = "abc"[1:4];"#, "bound"),
+        (r#"x = "abc"[2:1];"#, "slice"),
+        (r#"x = "abc"[true];"#, "type"),
+        (r#"x = 5[0];"#, "type"),
+        (r#"x = len(5);"#, "host"),
+    ] {
+        let error = Interpreter::new().eval(&parse_program(code)?).unwrap_err();
+        let matched = match check {
+            "mismatched" => matches!(error, EvalError::TypeMismatch { .. }),
+            "type" => matches!(error, EvalError::TypeError { .. }),
+            "index" => matches!(error, EvalError::IndexOutOfBounds { .. }),
+            "bound" => matches!(error, EvalError::SliceOutOfBounds { len: 3, .. }),
+            "slice" => matches!(
+                error,
+                EvalError::InvalidSlice {
+                    start: 2,
+                    end: 1,
+                    ..
+                }
+            ),
+            _ => matches!(error, EvalError::HostError { .. }),
+        };
+        assert!(matched, "{}: {:?}", code, error);
+    }
 
     Ok(())
 }
@@ -0,0 +1,200 @@
+use tiny_lang_parser::{ColorMode, Repl, Reply, Value};
+
+///Test that input is buffered until a `;` completes the statement
+#[test]
+fn test_multiline_input() {
+    let mut repl = Repl::new(ColorMode::Plain);
+
+    assert_eq!(repl.handle_line("x = 2 +"), Reply::Continue);
+    assert!(repl.is_continuing());
+    assert_eq!(repl.handle_line("  3;"), Reply::Output(String::new()));
+    assert!(!repl.is_continuing());
+
+    //Expression statements print their value, assignments do not
+    assert_eq!(repl.handle_line("x * 2;"), Reply::Output("10".to_string()));
+}
+
+///Test the :vars and :reset meta-commands
+#[test]
+fn test_vars_and_reset() {
+    let mut repl = Repl::new(ColorMode::Plain);
+    repl.handle_line("b = 2; a = 1;");
+
+    assert_eq!(
+        repl.handle_line(":vars"),
+        Reply::Output("a = 1\nb = 2".to_string())
+    );
+
+    repl.handle_line(":reset");
+    assert!(repl.interpreter().variables.is_empty());
+    assert!(matches!(repl.handle_line("a;"), Reply::Error(_)));
+}
+
+///Test that :save writes executed statements that :load can replay
+#[test]
+fn test_save_and_load() {
+    let path = std::env::temp_dir().join(format!("tiny_lang_repl_{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let mut repl = Repl::new(ColorMode::Plain);
+    repl.handle_line("x = 4;");
+    repl.handle_line("y = x * x;");
+    assert!(matches!(
+        repl.handle_line(&format!(":save {path}")),
+        Reply::Output(_)
+    ));
+
+    let mut fresh = Repl::new(ColorMode::Plain);
+    assert!(matches!(
+        fresh.handle_line(&format!(":load {path}")),
+        Reply::Output(_)
+    ));
+    assert_eq!(
+        fresh.interpreter().variables.get("y"),
+        Some(&Value::Int(16))
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+///Test that :ast shows the tree without evaluating it
+#[test]
+fn test_ast_command() {
+    let mut repl = Repl::new(ColorMode::Plain);
+
+    match repl.handle_line(":ast 1 + y") {
+        Reply::Output(text) => {
+            assert!(text.starts_with("Add("));
+            assert!(text.contains("Identifier(\n        \"y\",\n    ) @ 1:5"));
+        }
+        other => panic!("Expected AST output, got {other:?}"),
+    }
+}
+
+///Test that blocks are buffered until every brace is closed
+#[test]
+fn test_multiline_block() {
+    let mut repl = Repl::new(ColorMode::Plain);
+
+    assert_eq!(repl.handle_line("if (1 < 2) {"), Reply::Continue);
+    assert_eq!(repl.handle_line("  x = 1;"), Reply::Continue);
+    assert_eq!(
+        repl.handle_line("} else { x = 2; }"),
+        Reply::Output(String::new())
+    );
+    assert_eq!(repl.interpreter().variables.get("x"), Some(&Value::Int(1)));
+}
+
+///Test that comments neither complete nor hold open a statement
+#[test]
+fn test_comments_in_input() {
+    let mut repl = Repl::new(ColorMode::Plain);
+
+    assert_eq!(
+        repl.handle_line("x = 1; // done {"),
+        Reply::Output(String::new())
+    );
+    assert_eq!(repl.handle_line("y = x /* not yet;"), Reply::Continue);
+    assert_eq!(
+        repl.handle_line("still */ + 1;"),
+        Reply::Output(String::new())
+    );
+    assert_eq!(repl.interpreter().variables.get("y"), Some(&Value::Int(2)));
+}
+
+///Test that printed lines are shown in order with expression values
+#[test]
+fn test_print_output() {
+    let mut repl = Repl::new(ColorMode::Plain);
+
+    assert_eq!(
+        repl.handle_line("print 1; 2; print 3, 4;"),
+        Reply::Output("1\n2\n3 4".to_string())
+    );
+    match repl.handle_line("print 5; x = 1 / 0;") {
+        Reply::Error(text) => assert!(text.starts_with("5\nerror: division by zero")),
+        other => panic!("Expected an error, got {other:?}"),
+    }
+}
+
+///Test that :load shows what the loaded file prints before confirming
+#[test]
+fn test_load_output() {
+    let path =
+        std::env::temp_dir().join(format!("tiny_lang_repl_print_{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(path, "print \"hello\";\nx = 2;\nprint x * 3;\n").unwrap();
+
+    let mut repl = Repl::new(ColorMode::Plain);
+    assert_eq!(
+        repl.handle_line(&format!(":load {path}")),
+        Reply::Output(format!("hello\n6\nLoaded {path}."))
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+///Test that strings may hold terminators and span lines
+#[test]
+fn test_string_input() {
+    let mut repl = Repl::new(ColorMode::Plain);
+
+    assert_eq!(repl.handle_line(r#"s = "a; \{ // \"b"#), Reply::Continue);
+    assert_eq!(repl.handle_line(r#"c";"#), Reply::Output(String::new()));
+    assert_eq!(
+        repl.interpreter().variables.get("s"),
+        Some(&Value::from("a; { // \"b\nc"))
+    );
+}
+
+///Test that quotes and braces inside interpolations do not confuse input buffering
+#[test]
+fn test_interpolation_input() {
+    let mut repl = Repl::new(ColorMode::Plain);
+
+    assert_eq!(repl.handle_line(r#"s = "{"}"} {1 +"#), Reply::Continue);
+    assert_eq!(repl.handle_line(r#"1} ;";"#), Reply::Output(String::new()));
+    assert_eq!(repl.handle_line("s;"), Reply::Output("} 2 ;".to_string()));
+}
+
+///Test that a map literal spanning lines waits for the `;` after its `}`
+#[test]
+fn test_map_input() {
+    let mut repl = Repl::new(ColorMode::Plain);
+
+    assert_eq!(repl.handle_line("m = {"), Reply::Continue);
+    assert_eq!(repl.handle_line("  name: \"a\","), Reply::Continue);
+    assert_eq!(repl.handle_line("  size: 2 }"), Reply::Continue);
+    assert_eq!(repl.handle_line(";"), Reply::Output(String::new()));
+    //Braces after `)` and `else` open blocks, which end the statement
+    assert_eq!(
+        repl.handle_line("if (m.size > 1) { m.size = 3; } else { m = {}; }"),
+        Reply::Output(String::new())
+    );
+    assert_eq!(
+        repl.handle_line("m;"),
+        Reply::Output(r#"{"name": "a", "size": 3}"#.to_string())
+    );
+}
+
+///Test that the brace after a for loop's iterable opens a block
+#[test]
+fn test_for_input() {
+    let mut repl = Repl::new(ColorMode::Plain);
+
+    assert_eq!(repl.handle_line("total = 0;"), Reply::Output(String::new()));
+    assert_eq!(repl.handle_line("for i in 0..n {"), Reply::Continue);
+    assert_eq!(repl.handle_line("  total = total + i;"), Reply::Continue);
+    assert!(matches!(repl.handle_line("}"), Reply::Error(_)));
+    assert_eq!(
+        repl.handle_line("for x in [1, 2] { total = total + x; }"),
+        Reply::Output(String::new())
+    );
+    //A map literal after `in` still waits for its loop body
+    assert_eq!(repl.handle_line("for k in { a: 1 }"), Reply::Continue);
+    assert_eq!(
+        repl.handle_line("{ total = total + 1; }"),
+        Reply::Output(String::new())
+    );
+    assert_eq!(repl.handle_line("total;"), Reply::Output("4".to_string()));
+}
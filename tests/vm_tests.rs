@@ -0,0 +1,613 @@
+use anyhow::Result;
+use tiny_lang_parser::{
+    compile, parse_program, ASTNode, BufferSink, EvalError, FloatDivisionMode, Instruction,
+    IntegerDivisionMode, Interpreter, OverflowMode, Spanned, Value, Vm,
+};
+
+///Outcome of running the same program on both backends
+struct Runs {
+    tree_result: Result<(), EvalError>,
+    interpreter: Interpreter,
+    vm_result: Result<(), EvalError>,
+    vm: Vm,
+}
+
+///Runs `code` on both backends
+fn run_both(code: &str) -> Result<Runs> {
+    let ast = parse_program(code)?;
+
+    let mut interpreter = Interpreter::new();
+    let tree_result = interpreter.eval(&ast);
+
+    let mut vm = Vm::new();
+    let vm_result = vm.run(&compile(&ast));
+
+    Ok(Runs {
+        tree_result,
+        interpreter,
+        vm_result,
+        vm,
+    })
+}
+
+///Test that variable names are resolved to slots at compile time
+#[test]
+fn test_compile_resolves_slots() -> Result<()> {
+    let chunk = compile(&parse_program("x = 1; y = x + x;")?);
+
+    assert_eq!(chunk.names, vec!["x", "y"]);
+    assert!(chunk.code.contains(&Instruction::Load(0)));
+    assert!(chunk.code.contains(&Instruction::Store(1)));
+
+    Ok(())
+}
+
+///Test that the VM computes the same variables as the interpreter
+#[test]
+fn test_vm_matches_interpreter() -> Result<()> {
+    let code = r#"
+        a = 10;
+        b = 2;
+        c = (a + b) * 3 - 4 / 2;
+        a = c / b - a;
+    "#;
+    let runs = run_both(code)?;
+
+    runs.tree_result?;
+    runs.vm_result?;
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(runs.vm.variables().get("c"), Some(&Value::Int(34)));
+
+    Ok(())
+}
+
+///Test that short-circuiting operators behave identically on both backends
+#[test]
+fn test_vm_boolean_logic() -> Result<()> {
+    let code = r#"
+        x = 5;
+        a = x > 1 && x <= 5;
+        b = x == 4 || x != 5;
+        c = !(a && b) || missing;
+        d = false && missing;
+    "#;
+    let runs = run_both(code)?;
+
+    runs.tree_result?;
+    runs.vm_result?;
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(runs.vm.variables().get("c"), Some(&Value::Bool(true)));
+
+    Ok(())
+}
+
+///Test that conditionals run the same branches on both backends
+#[test]
+fn test_vm_conditionals() -> Result<()> {
+    let code = r#"
+        x = 3;
+        if (x > 5) { a = 1; } else if (x > 2) { a = 2; } else { a = 3; }
+        if (a == 2) { b = a * 10; }
+        if (false) { c = 1; }
+    "#;
+    let runs = run_both(code)?;
+
+    runs.tree_result?;
+    runs.vm_result?;
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(runs.vm.variables().get("b"), Some(&Value::Int(20)));
+    assert_eq!(runs.vm.variables().get("c"), None);
+
+    Ok(())
+}
+
+///Test that loops with break and continue run identically on both backends
+#[test]
+fn test_vm_loops() -> Result<()> {
+    let code = r#"
+        total = 0;
+        i = 0;
+        while (i < 5) {
+            i = i + 1;
+            j = 0;
+            while (true) {
+                j = j + 1;
+                if (j > i) { break; }
+                if (j == 2) { continue; }
+                total = total + j;
+            }
+        }
+    "#;
+    let runs = run_both(code)?;
+
+    runs.tree_result?;
+    runs.vm_result?;
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(runs.vm.variables().get("total"), Some(&Value::Int(27)));
+
+    Ok(())
+}
+
+///Test that function calls behave identically on both backends
+#[test]
+fn test_vm_functions() -> Result<()> {
+    let code = r#"
+        limit = 100;
+        fn collatz(n) {
+            steps = 0;
+            while (n != 1) {
+                if (steps >= limit) { return 0; }
+                if (n / 2 * 2 == n) { n = n / 2; } else { n = 3 * n + 1; }
+                steps = steps + 1;
+            }
+            return steps;
+        }
+        fn depth(n) {
+            if (n == 0) { return 0; }
+            return depth(n - 1) + 1;
+        }
+        a = collatz(27);
+        b = collatz(6);
+        c = depth(90);
+        d = max(pow(a, 2), isqrt(b * 8));
+        steps = 5;
+    "#;
+    let runs = run_both(code)?;
+
+    runs.tree_result?;
+    runs.vm_result?;
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(runs.vm.variables().get("a"), Some(&Value::Int(0)));
+    assert_eq!(runs.vm.variables().get("b"), Some(&Value::Int(8)));
+    assert_eq!(runs.vm.variables().get("c"), Some(&Value::Int(90)));
+
+    Ok(())
+}
+
+///Test that a return outside of any function ends the program on both backends
+#[test]
+fn test_vm_top_level_return() -> Result<()> {
+    //The parser rejects this, but it can be built by hand
+    let mut ast = parse_program("x = 1;")?;
+    ast.push(Spanned::from(ASTNode::Return(None)));
+    ast.extend(parse_program("y = 2;")?);
+
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(&ast)?;
+    let mut vm = Vm::new();
+    vm.run(&compile(&ast))?;
+
+    assert_eq!(vm.variables(), interpreter.variables);
+    assert_eq!(vm.variables().get("x"), Some(&Value::Int(1)));
+    assert_eq!(vm.variables().get("y"), None);
+
+    Ok(())
+}
+
+///Test that both backends report identical errors
+#[test]
+fn test_vm_errors_match_interpreter() -> Result<()> {
+    for code in [
+        "x = 1; y = x + z;",
+        "x = 2; y = x / (x - 2);",
+        "x = 1 < 2 && 3;",
+        "x = 1 == 2 || !0;",
+        "x = true < false;",
+        "x = 1; if (x) { y = 2; }",
+        "i = 0; while (i) { i = i + 1; }",
+        "x = f(1); fn f(a) { return a; }",
+        "fn f(a) { return a; } x = f(1, 2);",
+        "fn f() { return f(); } x = f();",
+        "x = 1; fn f() { y = x; x = 2; return y; } z = f();",
+        "fn f() { } x = f() + 1;",
+        "x = abs(1, 2);",
+        "x = isqrt(0 - 4);",
+        "x = max(1, true);",
+    ] {
+        let runs = run_both(code)?;
+
+        assert!(runs.tree_result.is_err());
+        assert_eq!(runs.tree_result, runs.vm_result);
+    }
+
+    Ok(())
+}
+
+///Test that an error inside a loop leaves the loop variable as it was
+///before the loop on both backends
+#[test]
+fn test_vm_error_restores_loop_variables() -> Result<()> {
+    for code in [
+        "i = 10; for i in 0..3 { x = 1 / (i - 1); }",
+        "for i in [1, 0] { x = 1 / i; }",
+        "i = 10; for i in 0..2 { for j in 0..2 { x = 1 / (i - j - 1); } }",
+        "fn f(n) { for i in 0..n { x = 1 / (i - 1); } return 0; } i = 10; for i in 0..2 { y = f(2); }",
+    ] {
+        let runs = run_both(code)?;
+
+        assert!(runs.tree_result.is_err());
+        assert_eq!(runs.tree_result, runs.vm_result);
+        assert_eq!(runs.vm.variables(), runs.interpreter.variables, "{}", code);
+    }
+
+    Ok(())
+}
+
+///Test that both backends apply the same overflow mode
+#[test]
+fn test_vm_overflow_modes() -> Result<()> {
+    let code = "big = 9223372036854775807; small = 0 - big - 1; a = big + 1; b = small / (0 - 1); c = -small;";
+    let ast = parse_program(code)?;
+    let chunk = compile(&ast);
+
+    for mode in [
+        OverflowMode::Checked,
+        OverflowMode::Wrapping,
+        OverflowMode::Saturating,
+    ] {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_overflow_mode(mode);
+        let mut vm = Vm::new();
+        vm.set_overflow_mode(mode);
+
+        assert_eq!(interpreter.eval(&ast), vm.run(&chunk));
+        assert_eq!(vm.variables(), interpreter.variables);
+    }
+
+    Ok(())
+}
+
+///Test that both backends promote to floats and divide by zero alike
+#[test]
+fn test_vm_floats() -> Result<()> {
+    let code = "a = 7 / 2.0; b = 1e3 * 2; c = .5 >= 0.25; d = 1 / 0.0;";
+    let ast = parse_program(code)?;
+    let chunk = compile(&ast);
+
+    for mode in [FloatDivisionMode::Error, FloatDivisionMode::Infinity] {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_float_division_mode(mode);
+        let mut vm = Vm::new();
+        vm.set_float_division_mode(mode);
+
+        assert_eq!(interpreter.eval(&ast), vm.run(&chunk));
+        assert_eq!(vm.variables(), interpreter.variables);
+    }
+
+    let mut vm = Vm::new();
+    vm.run(&chunk).ok();
+    assert_eq!(vm.variables().get("a"), Some(&Value::Float(3.5)));
+    assert_eq!(vm.variables().get("b"), Some(&Value::Float(2000.0)));
+
+    Ok(())
+}
+
+///Test that both backends apply the same integer division mode
+#[test]
+fn test_vm_exact_division() -> Result<()> {
+    let code = "a = 7 / 2; b = a * a - 1 / 4; c = a > 3; d = a / 0;";
+    let ast = parse_program(code)?;
+    let chunk = compile(&ast);
+
+    for mode in [IntegerDivisionMode::Truncate, IntegerDivisionMode::Exact] {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_integer_division_mode(mode);
+        let mut vm = Vm::new();
+        vm.set_integer_division_mode(mode);
+
+        assert_eq!(interpreter.eval(&ast), vm.run(&chunk));
+        assert_eq!(vm.variables(), interpreter.variables);
+    }
+
+    Ok(())
+}
+
+///Test that both backends agree on remainder, exponentiation and negation
+#[test]
+fn test_vm_arithmetic_operators() -> Result<()> {
+    let runs = run_both("a = -2 ** 2; b = 2 ** 3 ** 2; c = -7 % 3; d = +a - -b; e = 2.0 ** -2;")?;
+    assert_eq!(runs.tree_result, runs.vm_result);
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(runs.vm.variables().get("d"), Some(&Value::Int(508)));
+
+    for code in ["x = 1 % 0;", "x = 2 ** -1;", "x = -true;", "x = +false;"] {
+        let runs = run_both(code)?;
+        assert!(runs.tree_result.is_err(), "{} should fail", code);
+        assert_eq!(runs.tree_result, runs.vm_result, "{}", code);
+    }
+
+    Ok(())
+}
+
+///Test that both backends agree on bitwise and shift operators
+#[test]
+fn test_vm_bitwise_operators() -> Result<()> {
+    let runs = run_both("a = 5 | 1 << 3; b = ~a ^ 7 & 3; c = -16 >> 2; d = true ^ (a & 1 == 1);")?;
+    assert_eq!(runs.tree_result, runs.vm_result);
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(runs.vm.variables().get("a"), Some(&Value::Int(13)));
+
+    for code in [
+        "x = 1 << -1;",
+        "x = 1 & true;",
+        "x = ~false;",
+        "x = true << 1;",
+    ] {
+        let runs = run_both(code)?;
+        assert!(runs.tree_result.is_err(), "{} should fail", code);
+        assert_eq!(runs.tree_result, runs.vm_result, "{}", code);
+    }
+
+    Ok(())
+}
+
+///Test that print statements write the same lines on both backends
+#[test]
+fn test_vm_print() -> Result<()> {
+    let code = r#"
+        fn show(n) {
+            print n;
+            return n;
+        }
+        i = 0;
+        while (i < 3) {
+            print i, i * 1.5, i == 1;
+            i = i + 1;
+        }
+        print;
+        x = show(2) + 1;
+        print x / 0;
+        print x;
+    "#;
+    let ast = parse_program(code)?;
+
+    let tree_output = BufferSink::new();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(tree_output.clone());
+    let tree_result = interpreter.eval(&ast);
+
+    let vm_output = BufferSink::new();
+    let mut vm = Vm::new();
+    vm.set_output(vm_output.clone());
+    let vm_result = vm.run(&compile(&ast));
+
+    assert_eq!(vm_result, tree_result);
+    assert!(matches!(tree_result, Err(EvalError::DivisionByZero { .. })));
+    assert_eq!(vm_output.lines(), tree_output.lines());
+    assert_eq!(
+        tree_output.lines(),
+        ["0 0.0 false", "1 1.5 true", "2 3.0 false", "", "2"]
+    );
+
+    Ok(())
+}
+
+///Test that strings behave identically on both backends
+#[test]
+fn test_vm_strings() -> Result<()> {
+    let code = r#"
+        s = "héllo" + ", " + "world";
+        n = len(s);
+        a = s[1];
+        b = s[7:];
+        c = s[:5][1:3];
+        d = s < "help";
+        i = 0;
+        t = "";
+        while (i < len(b)) {
+            t = b[i] + t;
+            i = i + 1;
+        }
+    "#;
+    let runs = run_both(code)?;
+
+    runs.tree_result?;
+    runs.vm_result?;
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(runs.vm.variables().get("c"), Some(&Value::from("él")));
+    assert_eq!(runs.vm.variables().get("t"), Some(&Value::from("dlrow")));
+
+    for code in [
+        r#"x = "a" * "b";"#,
+        r#"s = "abc"; x = s[1:9];"#,
+        r#"s = "abc"; x = s[2:1];"#,
+        r#"s = "abc"; x = s[len(s)];"#,
+        r#"x = 1[0:];"#,
+    ] {
+        let runs = run_both(code)?;
+        assert!(runs.tree_result.is_err(), "{}", code);
+        assert_eq!(runs.vm_result, runs.tree_result, "{}", code);
+    }
+
+    Ok(())
+}
+
+///Test that both backends format interpolated strings the same way
+#[test]
+fn test_vm_interpolation() -> Result<()> {
+    let code = r#"
+        fn square(n) { return n * n; }
+        i = 0;
+        lines = "";
+        while (i < 3) {
+            lines = lines + "{i}^2 = {square(i)}; ";
+            i = i + 1;
+        }
+        r = "{1 / 2} {2.0 / 4} {"a" + "b"}";
+    "#;
+    let runs = run_both(code)?;
+
+    runs.tree_result?;
+    runs.vm_result?;
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(
+        runs.vm.variables().get("lines"),
+        Some(&Value::from("0^2 = 0; 1^2 = 1; 2^2 = 4; "))
+    );
+
+    let runs = run_both(r#"s = "{1 / 0}";"#)?;
+    assert!(runs.tree_result.is_err());
+    assert_eq!(runs.vm_result, runs.tree_result);
+
+    Ok(())
+}
+
+///Test that both backends build, read and update arrays the same way
+#[test]
+fn test_vm_arrays() -> Result<()> {
+    let code = r#"
+        fn squares(n) {
+            result = [0, 0, 0, 0];
+            i = 0;
+            while (i < n) {
+                result[i] = i * i;
+                i = i + 1;
+            }
+            return result;
+        }
+        fn swap(xs, i, j) {
+            t = xs[i];
+            xs[i] = xs[j];
+            xs[j] = t;
+            return xs;
+        }
+        grid = [[1, 2], [3, 4]];
+        grid[-1][-1] = grid[0][0] + 10;
+        swapped = swap([1, 2, 3], 0, -1);
+        middle = swapped[1:-1];
+        n = len(grid) + len(grid[0]);
+        s = squares(3);
+    "#;
+    let runs = run_both(code)?;
+
+    runs.tree_result?;
+    runs.vm_result?;
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(
+        runs.vm.variables().get("swapped").map(|v| v.to_string()),
+        Some("[3, 2, 1]".to_string())
+    );
+    assert_eq!(
+        runs.vm.variables().get("grid").map(|v| v.to_string()),
+        Some("[[1, 2], [3, 11]]".to_string())
+    );
+    assert_eq!(
+        runs.vm.variables().get("s").map(|v| v.to_string()),
+        Some("[0, 1, 4, 0]".to_string())
+    );
+
+    for code in [
+        "xs = [1, 2]; x = xs[2];",
+        "xs = [1, 2]; xs[-3] = 0;",
+        "xs = [1]; xs[0][0] = 1;",
+        "fn f() { ys[0] = 1; } f();",
+        "fn f() { ys = [0]; ys[1] = 1; } f();",
+        "x = ys[zs];",
+        "xs = [1]; x = xs[true];",
+        "fn f() { return ys[0]; } x = f();",
+        "fn f(xs) { return xs[zs]; } x = f([1]);",
+    ] {
+        let runs = run_both(code)?;
+        assert!(runs.tree_result.is_err(), "{}", code);
+        assert_eq!(runs.vm_result, runs.tree_result, "{}", code);
+    }
+
+    Ok(())
+}
+
+///Test that both backends build, read and update maps the same way
+#[test]
+fn test_vm_maps() -> Result<()> {
+    let code = r#"
+        fn point(x, y) { return { x: x, y: y }; }
+        fn shift(p) {
+            p.x = p.x + 1;
+            p["z"] = 0;
+            return p;
+        }
+        p = shift(point(1, 2));
+        config = { name: "demo", sizes: [1, 2] };
+        config.sizes[0] = config.sizes[1] * 10;
+        config.label = "{config.name}: {config.sizes}";
+        label = config.label;
+        names = keys(config);
+        n = len(config);
+    "#;
+    let runs = run_both(code)?;
+
+    runs.tree_result?;
+    runs.vm_result?;
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(
+        runs.vm.variables().get("p").map(|v| v.to_string()),
+        Some(r#"{"x": 2, "y": 2, "z": 0}"#.to_string())
+    );
+    assert_eq!(
+        runs.vm.variables().get("label"),
+        Some(&Value::from(r#"demo: [20, 2]"#))
+    );
+
+    for code in [
+        "m = {}; x = m.missing;",
+        "m = { a: 1 }; m.a.b = 2;",
+        "fn f() { m.a = 1; } f();",
+    ] {
+        let runs = run_both(code)?;
+        assert!(runs.tree_result.is_err(), "{}", code);
+        assert_eq!(runs.vm_result, runs.tree_result, "{}", code);
+    }
+
+    Ok(())
+}
+
+///Test that both backends run for loops and restore their variables the same way
+#[test]
+fn test_vm_for() -> Result<()> {
+    let code = r#"
+        fn find(xs, wanted) {
+            for i in 0..len(xs) {
+                if (xs[i] == wanted) { return i; }
+            }
+            return -1;
+        }
+        fn total(n) {
+            sum = 0;
+            for i in 1..=n { sum = sum + i; }
+            return sum;
+        }
+        found = "";
+        for w in "abz" {
+            if (w == "z") { continue; }
+            found = "{found}{find(["c", "b", "a"], w)}";
+        }
+        x = 7;
+        for x in { b: 2, a: 1 } { last = x; }
+        pairs = 0;
+        for a in 0..10 {
+            if (a == 3) { break; }
+            for b in [a, a] { pairs = pairs + total(b); }
+        }
+    "#;
+    let runs = run_both(code)?;
+
+    runs.tree_result?;
+    runs.vm_result?;
+    assert_eq!(runs.vm.variables(), runs.interpreter.variables);
+    assert_eq!(runs.vm.variables().get("found"), Some(&Value::from("21")));
+    assert_eq!(runs.vm.variables().get("x"), Some(&Value::Int(7)));
+    assert_eq!(runs.vm.variables().get("last"), Some(&Value::from("b")));
+    assert_eq!(runs.vm.variables().get("pairs"), Some(&Value::Int(8)));
+    assert_eq!(runs.vm.variables().get("w"), None);
+
+    for code in [
+        "for i in 0..\"3\" { }",
+        "for x in 1.5 { }",
+        "for i in 0..3 { y = i / (2 - i); }",
+    ] {
+        let runs = run_both(code)?;
+        assert!(runs.tree_result.is_err(), "{}", code);
+        assert_eq!(runs.vm_result, runs.tree_result, "{}", code);
+    }
+
+    Ok(())
+}